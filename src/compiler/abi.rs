@@ -0,0 +1,194 @@
+use crate::compiler::asm::*;
+use crate::type_checker::Typ;
+
+/// The calling conventions used to call the extern functions. Rack functions do
+/// not use them, their arguments and returned values stay on the rack stack.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Convention {
+    /// The windows x64 calling convention. The first four arguments are in
+    /// `rcx`, `rdx`, `r8` and `r9` (and in `xmm0` to `xmm3` for the floats),
+    /// the next ones are on the stack after 32 bytes of shadow space and `rsp`
+    /// is aligned on 16 bytes for the call. The value is returned in `rax` (or
+    /// `xmm0` for a float).
+    Win64,
+    /// The System V AMD64 calling convention (Linux, macOS). The first six
+    /// integer arguments are in `rdi`, `rsi`, `rdx`, `rcx`, `r8` and `r9` and
+    /// the first eight floats in `xmm0` to `xmm7`, the next ones are on the
+    /// stack without shadow space and `rsp` is aligned on 16 bytes for the
+    /// call. `al` holds the number of floats in registers for the functions
+    /// with a variable number of arguments. The value is returned in `rax` (or
+    /// `xmm0` for a float).
+    SysV,
+}
+
+impl Convention {
+    pub fn from_name(name:&str) -> Option<Self> {
+        match name {
+            "win64" => Some(Convention::Win64),
+            "sysv" => Some(Convention::SysV),
+            _ => None,
+        }
+    }
+
+    /// Move the arguments from the rack stack (the first argument is the top
+    /// of the stack) to where the callee expects them and align the stack for
+    /// the call. `rbx` keeps the top of the rack stack, the callee does not
+    /// change it in both conventions.
+    pub fn prologue(&self, args:&[Vec<Typ>]) -> Vec<Inst> {
+        let arg = |idx:usize| Op::Memory(format!("qword [rbx + {}]", idx * 8));
+        match self {
+            Convention::Win64 => {
+                let registers = [(Op::Rcx, Op::Xmm0), (Op::Rdx, Op::Xmm1), (Op::R8, Op::Xmm2), (Op::R9, Op::Xmm3)];
+                let stack_args = args.len().saturating_sub(registers.len());
+                let frame = (32 + stack_args * 8).div_ceil(16) * 16;
+                let mut output = vec![
+                    Inst::Mov(Op::Rbx, Op::Rsp),
+                    Inst::Inst2Op("and", Op::Rsp, Op::Immediate(-16)),
+                    Inst::Sub(Op::Rsp, Op::Immediate(frame as isize)),
+                ];
+                for idx in registers.len()..args.len() {
+                    output.append(&mut vec![
+                        Inst::Mov(Op::Rax, arg(idx)),
+                        Inst::Mov(Op::Memory(format!("qword [rsp + {}]", 32 + (idx - registers.len()) * 8)), Op::Rax),
+                    ]);
+                }
+                for (idx, (register, float_register)) in registers.iter().enumerate().take(args.len()) {
+                    output.push(Inst::Mov(register.clone(), arg(idx)));
+                    // A float is also in the integer register for the functions
+                    // with a variable number of arguments (like `printf`).
+                    if args[idx] == vec![Typ::Float] {
+                        output.push(Inst::Inst2Op("movq", float_register.clone(), register.clone()));
+                    }
+                }
+                output
+            },
+            Convention::SysV => {
+                let mut registers = vec![Op::Rdi, Op::Rsi, Op::Rdx, Op::Rcx, Op::R8, Op::R9].into_iter();
+                let mut float_registers = vec![
+                    Op::Xmm0, Op::Xmm1, Op::Xmm2, Op::Xmm3, Op::Xmm4, Op::Xmm5, Op::Xmm6, Op::Xmm7,
+                ].into_iter();
+                let mut moves = vec![];
+                let mut stack_args = vec![];
+                let mut floats = 0;
+                for (idx, typ) in args.iter().enumerate() {
+                    if typ == &vec![Typ::Float] {
+                        match float_registers.next() {
+                            Some(register) => {
+                                moves.push(Inst::Inst2Op("movq", register, arg(idx)));
+                                floats += 1;
+                            },
+                            None => stack_args.push(idx),
+                        }
+                    } else {
+                        match registers.next() {
+                            Some(register) => moves.push(Inst::Mov(register, arg(idx))),
+                            None => stack_args.push(idx),
+                        }
+                    }
+                }
+                let mut output = vec![
+                    Inst::Mov(Op::Rbx, Op::Rsp),
+                    Inst::Inst2Op("and", Op::Rsp, Op::Immediate(-16)),
+                ];
+                if !stack_args.is_empty() {
+                    let frame = (stack_args.len() * 8).div_ceil(16) * 16;
+                    output.push(Inst::Sub(Op::Rsp, Op::Immediate(frame as isize)));
+                }
+                for (slot, idx) in stack_args.into_iter().enumerate() {
+                    output.append(&mut vec![
+                        Inst::Mov(Op::Rax, arg(idx)),
+                        Inst::Mov(Op::Memory(format!("qword [rsp + {}]", slot * 8)), Op::Rax),
+                    ]);
+                }
+                output.append(&mut moves);
+                output.push(Inst::Mov(Op::Rax, Op::Immediate(floats)));
+                output
+            },
+        }
+    }
+
+    /// A native entry point to the rack function `target`, for the foreign
+    /// code. The nonvolatile registers are saved (`rsi` and `rdi` are only
+    /// nonvolatile with `Win64`, they are saved anyway), the arguments are
+    /// pushed on the rack stack (the first argument on the top), then the
+    /// function is called and its returned value is moved to `rax` (or `xmm0`).
+    pub fn trampoline(&self, label:&str, target:&str, args:&[Vec<Typ>], returned:Option<Typ>) -> Vec<Inst> {
+        let saved = [Op::Rbx, Op::Rbp, Op::Rsi, Op::Rdi, Op::R12, Op::R13, Op::R14, Op::R15];
+        // The stack arguments are after the saved registers and the return
+        // address (and the shadow space with `Win64`).
+        let stack_args = saved.len() * 8 + 8 + match self {
+            Convention::Win64 => 32,
+            Convention::SysV => 0,
+        };
+        let stack_arg = |slot:usize| vec![Inst::Push(Op::Memory(format!("qword [rbp + {}]", stack_args + slot * 8)))];
+        let float_arg = |register:Op| vec![Inst::Inst2Op("movq", Op::Rax, register), Inst::Push(Op::Rax)];
+        // The instructions pushing each argument, in the order of the arguments.
+        let mut pushes:Vec<Vec<Inst>> = vec![];
+        match self {
+            Convention::Win64 => {
+                let registers = [(Op::Rcx, Op::Xmm0), (Op::Rdx, Op::Xmm1), (Op::R8, Op::Xmm2), (Op::R9, Op::Xmm3)];
+                for (idx, typ) in args.iter().enumerate() {
+                    pushes.push(match registers.get(idx) {
+                        Some((_, float_register)) if typ == &vec![Typ::Float] => float_arg(float_register.clone()),
+                        Some((register, _)) => vec![Inst::Push(register.clone())],
+                        None => stack_arg(idx - registers.len()),
+                    });
+                }
+            },
+            Convention::SysV => {
+                let mut registers = vec![Op::Rdi, Op::Rsi, Op::Rdx, Op::Rcx, Op::R8, Op::R9].into_iter();
+                let mut float_registers = vec![
+                    Op::Xmm0, Op::Xmm1, Op::Xmm2, Op::Xmm3, Op::Xmm4, Op::Xmm5, Op::Xmm6, Op::Xmm7,
+                ].into_iter();
+                let mut slot = 0;
+                for typ in args {
+                    let register = if typ == &vec![Typ::Float] {
+                        float_registers.next().map(float_arg)
+                    } else {
+                        registers.next().map(|register| vec![Inst::Push(register)])
+                    };
+                    pushes.push(register.unwrap_or_else(|| {
+                        slot += 1;
+                        stack_arg(slot - 1)
+                    }));
+                }
+            },
+        }
+        let mut output = vec![
+            Inst::Inst1Op("global", Op::Label(label.to_string())),
+            Inst::Label(format!("{}:", label)),
+        ];
+        output.extend(saved.iter().map(|register| Inst::Push(register.clone())));
+        output.push(Inst::Mov(Op::Rbp, Op::Rsp));
+        pushes.into_iter().rev().for_each(|mut push| output.append(&mut push));
+        output.push(Inst::Call(Op::Label(target.to_string())));
+        match returned {
+            Some(Typ::Float) => output.append(&mut vec![
+                Inst::Pop(Op::Rax),
+                Inst::Inst2Op("movq", Op::Xmm0, Op::Rax),
+            ]),
+            Some(_) => output.push(Inst::Pop(Op::Rax)),
+            None => (),
+        }
+        output.push(Inst::Mov(Op::Rsp, Op::Rbp));
+        output.extend(saved.iter().rev().map(|register| Inst::Pop(register.clone())));
+        output.push(Inst::Ret);
+        output
+    }
+
+    /// Restore the stack without the arguments and push the returned value, if
+    /// the function does not return `void`. Both conventions return in the same
+    /// registers.
+    pub fn epilogue(&self, args_count:usize, returned:Option<Typ>) -> Vec<Inst> {
+        let mut output = vec![Inst::Lea(Op::Rsp, Op::Memory(format!("[rbx + {}]", args_count * 8)))];
+        match returned {
+            Some(Typ::Float) => output.append(&mut vec![
+                Inst::Inst2Op("movq", Op::Rax, Op::Xmm0),
+                Inst::Push(Op::Rax),
+            ]),
+            Some(_) => output.push(Inst::Push(Op::Rax)),
+            None => (),
+        }
+        output
+    }
+}
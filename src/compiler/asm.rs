@@ -1,3 +1,5 @@
+use std::fmt;
+
 #[derive(Clone, PartialEq)]
 pub enum Op {
     Rax,
@@ -5,6 +7,7 @@ pub enum Op {
     Rcx,
     Rdx,
     Rsi,
+    Rdi,
     Rsp,
     Rbp,
     R8,
@@ -15,20 +18,29 @@ pub enum Op {
     R13,
     R14,
     R15,
+    Xmm0,
+    Xmm1,
+    Xmm2,
+    Xmm3,
+    Xmm4,
+    Xmm5,
+    Xmm6,
+    Xmm7,
     Label(String),
     Immediate(isize),
     Memory(String),
     Ignore,
 }
 
-impl Op {
-    pub fn to_string(&self) -> String {
-        match self {
+impl fmt::Display for Op {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        let op = match self {
             Op::Rax => "rax".to_string(),
             Op::Rbx => "rbx".to_string(),
             Op::Rcx => "rcx".to_string(),
             Op::Rdx => "rdx".to_string(),
             Op::Rsi => "rsi".to_string(),
+            Op::Rdi => "rdi".to_string(),
             Op::Rsp => "rsp".to_string(),
             Op::Rbp => "rbp".to_string(),
             Op::R8 => "r8".to_string(),
@@ -39,23 +51,36 @@ impl Op {
             Op::R13 => "r13".to_string(),
             Op::R14 => "r14".to_string(),
             Op::R15 => "r15".to_string(),
+            Op::Xmm0 => "xmm0".to_string(),
+            Op::Xmm1 => "xmm1".to_string(),
+            Op::Xmm2 => "xmm2".to_string(),
+            Op::Xmm3 => "xmm3".to_string(),
+            Op::Xmm4 => "xmm4".to_string(),
+            Op::Xmm5 => "xmm5".to_string(),
+            Op::Xmm6 => "xmm6".to_string(),
+            Op::Xmm7 => "xmm7".to_string(),
             Op::Label(label) => label.to_string().replace("::", "_"),
             Op::Immediate(integer) => integer.to_string(),
             Op::Memory(mem) => mem.clone(),
             Op::Ignore => "".to_string(),
-        }   
+        };
+        write!(f, "{}", op)
     }
+}
+
+impl Op {
     pub fn is_register(&self) -> bool {
-        match self {
-            Op::Rax | Op::Rbx | Op::Rcx | Op::Rdx | Op::Rsi | Op::Rsp |
+        matches!(
+            self,
+            Op::Rax | Op::Rbx | Op::Rcx | Op::Rdx | Op::Rsi | Op::Rdi | Op::Rsp |
             Op::Rbp | Op::R8  | Op::R9  | Op::R10 | Op::R11 | Op::R12 |
-            Op::R13 | Op::R14 | Op::R15 => true,
-            _ => false,
-        }
+            Op::R13 | Op::R14 | Op::R15
+        )
     }
 }
 
 #[derive(PartialEq)]
+#[allow(clippy::enum_variant_names)]
 pub enum Inst {
     Push(Op),
     Pop(Op),
@@ -72,28 +97,40 @@ pub enum Inst {
     Inst1Op(&'static str, Op),
     Label(String),
     Ret,
+    /// An instruction written by the user in an `asm` block.
+    Raw(String),
+    /// A comment, like the location of the token producing the next
+    /// instructions.
+    Comment(String),
+    /// A `%line` directive, the next instructions come from this row of this
+    /// file.
+    Line(usize, String),
     Ignore,
 }
 
-impl Inst {
-    pub fn to_string(&self) -> String {
-        match self {
-            Inst::Push(op) => format!("\tpush {}\n", op.to_string()),
-            Inst::Pop(op) => format!("\tpop {}\n", op.to_string()),
-            Inst::Mov(dest, src) => format!("\tmov {}, {}\n", dest.to_string(), src.to_string()),
-            Inst::Lea(dest, src) => format!("\tlea {}, {}\n", dest.to_string(), src.to_string()),
-            Inst::Call(op) => format!("\tcall {}\n", op.to_string()),
-            Inst::Add(dest, src) => format!("\tadd {}, {}\n", dest.to_string(), src.to_string()),
-            Inst::Sub(dest, src) => format!("\tsub {}, {}\n", dest.to_string(), src.to_string()),
-            Inst::Mul(op) => format!("\tmul {}\n", op.to_string()),
-            Inst::Div(op) => format!("\tdiv {}\n", op.to_string()),
-            Inst::Xor(dest, src) => format!("\txor {}, {}\n", dest.to_string(), src.to_string()),
-            Inst::Cmp(dest, src) => format!("\tcmp {}, {}\n", dest.to_string(), src.to_string()),
-            Inst::Inst2Op(inst, dest, src) => format!("\t{} {}, {}\n", inst, dest.to_string(), src.to_string()),
-            Inst::Inst1Op(inst, op) => format!("\t{} {}\n", inst, op.to_string()),
+impl fmt::Display for Inst {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        let inst = match self {
+            Inst::Push(op) => format!("\tpush {}\n", op),
+            Inst::Pop(op) => format!("\tpop {}\n", op),
+            Inst::Mov(dest, src) => format!("\tmov {}, {}\n", dest, src),
+            Inst::Lea(dest, src) => format!("\tlea {}, {}\n", dest, src),
+            Inst::Call(op) => format!("\tcall {}\n", op),
+            Inst::Add(dest, src) => format!("\tadd {}, {}\n", dest, src),
+            Inst::Sub(dest, src) => format!("\tsub {}, {}\n", dest, src),
+            Inst::Mul(op) => format!("\tmul {}\n", op),
+            Inst::Div(op) => format!("\tdiv {}\n", op),
+            Inst::Xor(dest, src) => format!("\txor {}, {}\n", dest, src),
+            Inst::Cmp(dest, src) => format!("\tcmp {}, {}\n", dest, src),
+            Inst::Inst2Op(inst, dest, src) => format!("\t{} {}, {}\n", inst, dest, src),
+            Inst::Inst1Op(inst, op) => format!("\t{} {}\n", inst, op),
             Inst::Label(label) => format!("{}\n", label).replace("::", "_"),
             Inst::Ret => "\tret\n".to_string(),
+            Inst::Raw(inst) => format!("\t{}\n", inst),
+            Inst::Comment(comment) => format!("; {}\n", comment),
+            Inst::Line(row, filename) => format!("%line {}+0 {}\n", row, filename),
             Inst::Ignore => "".to_string(),
-        }
+        };
+        write!(f, "{}", inst)
     }
 }
\ No newline at end of file
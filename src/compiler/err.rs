@@ -5,6 +5,7 @@ pub enum ErrTyp {
     User,
     CommandLine,
     Function,
+    Warning,
 }
 
 pub struct Err {
@@ -59,6 +60,18 @@ impl Err {
         }
     }
 
+    /// A problem that does not stop the compilation, it is only printed.
+    pub fn warning(message:String, location:(usize, usize, String), token_len:usize) -> Self {
+        Self {
+            typ:ErrTyp::Warning,
+            message,
+            location:(location.0, location.1),
+            filename:location.2,
+            token_len,
+            function_line:None,
+        }
+    }
+
     pub fn command_line(message:String) -> Self {
         Self {
             typ:ErrTyp::CommandLine,
@@ -95,6 +108,16 @@ impl Err {
                     self.line_with_error(),
                 )
             },
+            ErrTyp::Warning => {
+                println!(
+                    "{}: {}\n {} {}\n{}",
+                    "warning".yellow().bold(),
+                    self.message.bold(),
+                    "-->".cyan(),
+                    self.location_string(),
+                    self.line_with_error(),
+                )
+            },
             ErrTyp::Function => {
                 println!(
                     "{}: {}\n {} {}\n{}\n{: >5}{}\n{}",
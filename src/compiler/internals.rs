@@ -1,42 +1,163 @@
-use std::collections::{HashMap};
+use std::collections::{HashMap, HashSet};
+use crate::compiler::asm::{Inst, Op};
+use crate::compiler::abi;
 
 /// Internals variables used during compilation.
 pub struct Internals {
     pub strings:Vec<String>,
+    pub reservations:Vec<(String, usize)>,
+    /// The extern functions called by the program.
+    pub externs:Vec<String>,
+    /// The tokens targeted by more than one jump (like the `end` of a `fn`
+    /// with a `ret` or the arms of a `case`).
+    pub shared_targets:HashSet<usize>,
     pub idx:usize,
     pub addresses_usage:HashMap<usize, usize>,
     pub should_increment:bool,
     pub current_variable:Option<String>,
     pub variables:Vec<String>,
     pub location:(usize, usize, String),
+    /// The calling convention of the extern functions.
+    pub convention:abi::Convention,
+    /// The number of runtime checks emitted, used for their labels.
+    pub guards:usize,
+    /// The `as` of each `for` loop. The index and the end of a loop are not
+    /// variables, they are stored for each depth of calls so that a recursive
+    /// call does not overwrite them.
+    pub loops:Vec<usize>,
 }
 
 impl Internals {
     pub fn new() -> Self {
         Self {
             strings:vec![],
+            reservations:vec![],
+            externs:vec![],
+            shared_targets:HashSet::new(),
             idx:0,
             addresses_usage:HashMap::new(),
             should_increment:false,
             current_variable:None,
             variables:vec![],
             location:(0, 0, "".to_string()),
+            convention:abi::Convention::Win64,
+            guards:0,
+            loops:vec![],
         }
     }
 
+    /// The index of a string, identical strings share the same label.
     pub fn push_string(&mut self, string:String) -> usize {
-        self.strings.push(string);
-        self.strings.len() - 1
+        match self.strings.iter().position(|other| other == &string) {
+            Some(idx) => idx,
+            None => {
+                self.strings.push(string);
+                self.strings.len() - 1
+            }
+        }
+    }
+
+    /// The index of a variable, the variable is created if it does not exist.
+    pub fn variable_idx(&mut self, variable:String) -> usize {
+        match self.variables.iter().position(|name| name == &variable) {
+            Some(idx) => idx,
+            None => {
+                self.variables.push(variable);
+                self.variables.len() - 1
+            }
+        }
     }
 
-    pub fn compile_strings(&mut self) -> String {
+    /// The slot of the index (or of the end, with `is_end`) of the `for` loop
+    /// of the `as` at `as_idx`. `rax` must hold the current depth of calls and
+    /// `rcx` the address of `_mem@loops`, see `loop_frame`.
+    pub fn loop_slot(&mut self, as_idx:usize, is_end:bool) -> Op {
+        let loop_idx = match self.loops.iter().position(|idx| *idx == as_idx) {
+            Some(loop_idx) => loop_idx,
+            None => {
+                self.loops.push(as_idx);
+                self.loops.len() - 1
+            }
+        };
+        Op::Memory(format!("qword [rcx + rax * 8 + {} * _mem@loop_frame]", loop_idx * 2 + is_end as usize))
+    }
+
+    /// Load the current depth of calls in `rax` and the address of the slots
+    /// of the `for` loops in `rcx`.
+    pub fn loop_frame(&self) -> Vec<Inst> {
+        vec![
+            Inst::Mov(Op::Rax, Op::Memory("qword [_mem@ret_ptr_idx]".to_string())),
+            Inst::Lea(Op::Rcx, Op::Memory("[_mem@loops]".to_string())),
+        ]
+    }
+
+    pub fn push_extern(&mut self, name:String) {
+        if !self.externs.contains(&name) {
+            self.externs.push(name);
+        }
+    }
+
+    pub fn push_reservation(&mut self, name:String, size:usize) {
+        self.reservations.push((name, size));
+    }
+
+    /// The static buffers declared with `memory`, they are reserved in the
+    /// `.bss` segment.
+    pub fn compile_reservations(&self) -> String {
         let mut output = "".to_string();
-        for (idx, string) in self.strings.iter_mut().enumerate() {
+        for (name, size) in self.reservations.iter() {
+            output.push_str(&format!("	_static@{}: resb {}\n", name.replace("::", "_"), size));
+        }
+        output
+    }
+
+    /// The declarations of the extern functions, nasm ignores the ones that
+    /// are already declared by the templates.
+    pub fn compile_externs(&self) -> String {
+        let mut output = "".to_string();
+        for name in self.externs.iter() {
+            output.push_str(&format!("\textern {}\n", name));
+        }
+        output
+    }
+
+    /// The strings are read only, they are in the `.rdata` segment. The length
+    /// of each string (without the final 0) is stored in the qword before it for
+    /// the slices.
+    pub fn compile_strings(&self) -> String {
+        let mut output = "\nsegment .rdata\n".to_string();
+        for (idx, string) in self.strings.iter().enumerate() {
+            output.push_str(&format!("\talign 8\n\tdq {}\n", string.len()));
             output.push_str(&format!("\tstr_{} db \"{}\",0\n", idx, string));
         }
         output
     }
 
+    /// The label of a token targeted by more than one jump. Unlike the other
+    /// addresses, a single label can be the target of many jumps.
+    pub fn shared_address(&self, idx:usize) -> String {
+        format!("TARGET_{}", idx)
+    }
+
+    /// The address to jump to in order to reach the token at `idx`.
+    pub fn jump_address(&mut self, idx:usize) -> String {
+        if self.shared_targets.contains(&idx) {
+            self.shared_address(idx)
+        } else {
+            self.compute_address(idx)
+        }
+    }
+
+    /// The labels of the token currently compiled, it can be reached with
+    /// `compute_address` and with `shared_address` if it is a shared target.
+    pub fn labels(&mut self) -> Vec<Inst> {
+        let mut labels = vec![Inst::Label(format!("{}:", self.compute_address(self.idx)))];
+        if self.shared_targets.contains(&self.idx) {
+            labels.push(Inst::Label(format!("{}:", self.shared_address(self.idx))));
+        }
+        labels
+    }
+
     /// Compute the addresses to be different in every macro, even if a macro is
     /// used more than once. Every time an address is called (an address is just
     /// the token idx), the counter for that address is incremented by one. We
@@ -1,13 +1,22 @@
 mod template;
+pub mod abi;
 pub mod asm;
 pub mod internals;
 pub mod err;
 
 use std::fs::File;
 use std::io::prelude::*;
-use std::collections::HashMap;
-use crate::parser::token;
-use crate::type_checker;
+use std::collections::{HashMap, HashSet};
+use crate::parser::{self, token};
+use crate::parser::typ::control_flow;
+
+/// The default maximum depth of the calls, it can be changed with the
+/// `--max-depth` flag.
+pub const DEFAULT_MAX_DEPTH:usize = 1024;
+
+/// The size of the data stack checked with `--debug-runtime`, a bit less than
+/// the 1 MiB reserved for the stack by the windows linker.
+const DATA_STACK_SIZE:isize = 0xF0000;
 
 enum Platform {
     Windows,
@@ -16,20 +25,34 @@ enum Platform {
 pub struct Compiler {
     output:String,
     platform:Platform,
-    functions:HashMap<String, (usize, usize, Vec<Vec<type_checker::Typ>>, Vec<Vec<type_checker::Typ>>, bool)>,
-    consts:HashMap<String, isize>,
+    functions:parser::Functions,
+    consts:HashMap<String, parser::constant::Value>,
     internals:internals::Internals,
+    /// The maximum number of functions running at the same time.
+    pub max_depth:usize,
+    /// Check the data stack at runtime, the program stops with the location
+    /// of the faulty token instead of crashing.
+    pub debug_runtime:bool,
+    /// Print a warning for every function that is never called, they are not
+    /// compiled.
+    pub print_unused:bool,
+    /// The calling convention of the extern functions.
+    pub convention:abi::Convention,
 }
 
 impl Compiler {
     pub fn new(
-        functions:HashMap<String, (usize, usize, Vec<Vec<type_checker::Typ>>, Vec<Vec<type_checker::Typ>>, bool)>,
-        consts:HashMap<String, isize>,
+        functions:parser::Functions,
+        consts:HashMap<String, parser::constant::Value>,
     ) -> Self {
         Self {
             output:"".to_string(),
             platform:Platform::Windows,
             internals:internals::Internals::new(),
+            max_depth:DEFAULT_MAX_DEPTH,
+            debug_runtime:false,
+            print_unused:false,
+            convention:abi::Convention::Win64,
             functions,
             consts,
         }
@@ -37,26 +60,72 @@ impl Compiler {
 
     pub fn compile<'a>(&mut self, tokens:Vec<token::Token<'a>>) {
         self.init_output();
+        self.internals.convention = self.convention.clone();
+        self.internals.shared_targets = shared_targets(&tokens);
+        let reachable = reachable_functions(&tokens, &self.functions);
+        if self.print_unused {
+            self.print_unused_functions(&tokens, &reachable);
+        }
         let mut functions = vec![];
-        for (identifier, (start, end, _, _, _)) in self.functions.iter() {
+        // The number of values taken and returned by each function, the values
+        // returned by a function ignoring its returns (`!`) are not checked.
+        let values = |types:&Vec<Vec<crate::type_checker::Typ>>| {
+            types.iter().filter(|typ| **typ != vec![crate::type_checker::Typ::Void]).count()
+        };
+        let mut signatures:Vec<(String, usize, usize, usize, Option<usize>)> = self.functions.iter().map(|(identifier, (start, end, args, returns, ignore_return))| {
+            (identifier.clone(), *start, *end, values(args), if *ignore_return { None } else { Some(values(returns)) })
+        }).collect();
+        // The functions are compiled in the order of the source, the output
+        // must be the same for every build of the same program.
+        signatures.sort_by_key(|signature| signature.1);
+        for (identifier, start, end, args, returns) in signatures.iter().filter(|signature| reachable.contains(&signature.0)) {
             self.internals.idx = *start;
+            // The name of the function is used in the stack overflow error.
+            let name_idx = self.internals.push_string(identifier.to_string());
             functions.append(&mut vec![
                 asm::Inst::Label(format!("{}:", identifier)),
+                asm::Inst::Lea(asm::Op::Rcx, asm::Op::Memory(format!("[str_{}]", name_idx))),
                 asm::Inst::Call(asm::Op::Label("_std@store_ret_ptr".to_string()))
             ]);
+            if self.debug_runtime {
+                let name = &tokens[*start - 2];
+                self.internals.location = (name.row, name.col, name.filename.clone());
+                functions.append(&mut self.guard_fn_entry(*args));
+            }
             while self.internals.idx < *end {
                 match tokens[self.internals.idx].compile(&mut self.internals, &self.functions, &self.consts) {
-                    Ok(mut toks) => functions.append(&mut toks),
+                    Ok(toks) => {
+                        functions.append(&mut self.annotate(&tokens[self.internals.idx], &toks));
+                        functions.append(&mut self.guard_pops(toks));
+                    },
                     Err(err) => {
                         err.panic();
                     }
                 }
                 self.internals.idx += 1;
             }
+            if self.internals.shared_targets.contains(end) {
+                functions.push(asm::Inst::Label(format!("{}:", self.internals.shared_address(*end))));
+            }
+            if let (true, Some(returns)) = (self.debug_runtime, returns) {
+                let end = &tokens[*end];
+                self.internals.location = (end.row, end.col, end.filename.clone());
+                functions.append(&mut self.guard_fn_exit(*args, *returns));
+            }
             functions.append(&mut vec![
                 asm::Inst::Call(asm::Op::Label("_std@load_ret_ptr".to_string())),
                 asm::Inst::Ret,
             ]);
+            // The `export` is just before the `fn` of an exported function.
+            let export = start.checked_sub(4).map(|idx| &tokens[idx].typ);
+            if let Some(parser::typ::Typ::Sys(parser::typ::sys::Token::Exported(function))) = export {
+                functions.append(&mut self.internals.convention.trampoline(
+                    &format!("_export@{}", identifier),
+                    identifier,
+                    &function.args,
+                    function.returned(),
+                ));
+            }
         }
         functions.iter().for_each(|inst| {
             self.push_op(&inst.to_string());
@@ -64,10 +133,16 @@ impl Compiler {
         self.push_op("\n");
         self.push_op("_start:\n");
         let mut output = vec![];
+        if self.debug_runtime {
+            output.push(asm::Inst::Mov(asm::Op::Memory("qword [_mem@stack_base]".to_string()), asm::Op::Rsp));
+        }
         self.internals.idx = 0;
         while self.internals.idx < tokens.len() {
             match tokens[self.internals.idx].compile(&mut self.internals, &self.functions, &self.consts) {
-                Ok(mut toks) => output.append(&mut toks),
+                Ok(toks) => {
+                    output.append(&mut self.annotate(&tokens[self.internals.idx], &toks));
+                    output.append(&mut self.guard_pops(toks));
+                },
                 Err(err) => err.panic()
             }
             self.internals.idx += 1;
@@ -87,13 +162,185 @@ impl Compiler {
 
     fn compile_strings(&mut self) {
         self.push_op("\n");
-        self.push_op("segment .data\n\t_mem@ret_ptr_idx dw 0\n");
+        let externs = self.internals.compile_externs();
+        self.push_op(&externs);
+        self.push_op(template::data());
         let strings = self.internals.compile_strings();
         self.push_op(&strings);
+        if !self.internals.reservations.is_empty() {
+            self.push_op("\nsegment .bss\n");
+            let reservations = self.internals.compile_reservations();
+            self.push_op(&reservations);
+        }
+        if !self.internals.loops.is_empty() {
+            self.push_op(&template::loops(self.internals.loops.len(), self.max_depth));
+        }
     }
 
 
 
+    /// Warn about the functions that are never called, in the order of the
+    /// files.
+    fn print_unused_functions(&self, tokens:&[token::Token], reachable:&HashSet<String>) {
+        let mut unused:Vec<&token::Token> = self.functions.iter()
+            .filter(|(identifier, _)| !reachable.contains(*identifier))
+            .map(|(_, (start, _, _, _, _))| &tokens[*start - 2])
+            .collect();
+        unused.sort_by(|lhs, rhs| (&lhs.filename, lhs.row, lhs.col).cmp(&(&rhs.filename, rhs.row, rhs.col)));
+        for name in unused {
+            err::Err::warning(
+                format!("The function `{}` is never used, it is not compiled.", name.value),
+                (name.row, name.col, name.filename.clone()),
+                name.len(),
+            ).print();
+        }
+    }
+
+    /// Link the instructions of a token to its source: a comment with the
+    /// location and the text of the token and a `%line` directive, for the
+    /// debug information of nasm.
+    fn annotate(&self, token:&token::Token, insts:&[asm::Inst]) -> Vec<asm::Inst> {
+        if insts.iter().all(|inst| matches!(inst, asm::Inst::Ignore)) {
+            return vec![];
+        }
+        let text = match token.typ {
+            parser::typ::Typ::Str(_) => format!("\"{}\"", token.value),
+            parser::typ::Typ::Slice(_) => format!("s\"{}\"", token.value),
+            parser::typ::Typ::Char(_) => format!("'{}'", token.value),
+            _ => token.value.to_string(),
+        };
+        vec![
+            asm::Inst::Comment(format!("{}:{}:{} {}", token.filename, token.row, token.col, text)),
+            asm::Inst::Line(token.row, token.filename.clone()),
+        ]
+    }
+
+    /// Check that the data stack is not empty before every `pop` of a token. A
+    /// call to `_std@put_variable` pops the value to store below the index of
+    /// the variable, it is checked before the call. The arguments of an extern
+    /// call must have been on the data stack once they are removed
+    /// (`lea rsp, [rbx + <args>]`).
+    fn guard_pops(&mut self, insts:Vec<asm::Inst>) -> Vec<asm::Inst> {
+        let is_guarded = |inst:&asm::Inst| match inst {
+            asm::Inst::Pop(_) | asm::Inst::Lea(asm::Op::Rsp, _) => true,
+            asm::Inst::Call(asm::Op::Label(label)) => label == "_std@put_variable",
+            _ => false,
+        };
+        if !self.debug_runtime || !insts.iter().any(is_guarded) {
+            return insts;
+        }
+        let location = self.location_string();
+        let mut output = vec![];
+        for inst in insts {
+            match inst {
+                asm::Inst::Pop(_) => {
+                    output.append(&mut self.guard(
+                        vec![asm::Inst::Cmp(asm::Op::Rsp, asm::Op::Memory("qword [_mem@stack_base]".to_string()))],
+                        "jb",
+                        "_std@stack_underflow_msg",
+                        location,
+                    ));
+                    output.push(inst);
+                },
+                asm::Inst::Call(asm::Op::Label(ref label)) if label == "_std@put_variable" => {
+                    output.append(&mut self.guard(
+                        vec![
+                            asm::Inst::Lea(asm::Op::Rax, asm::Op::Memory("[rsp + 8]".to_string())),
+                            asm::Inst::Cmp(asm::Op::Rax, asm::Op::Memory("qword [_mem@stack_base]".to_string())),
+                        ],
+                        "jb",
+                        "_std@stack_underflow_msg",
+                        location,
+                    ));
+                    output.push(inst);
+                },
+                asm::Inst::Lea(asm::Op::Rsp, _) => {
+                    output.push(inst);
+                    output.append(&mut self.guard(
+                        vec![asm::Inst::Cmp(asm::Op::Rsp, asm::Op::Memory("qword [_mem@stack_base]".to_string()))],
+                        "jbe",
+                        "_std@stack_underflow_msg",
+                        location,
+                    ));
+                },
+                _ => output.push(inst),
+            }
+        }
+        output
+    }
+
+    /// Check that the arguments of a function are on the data stack and that
+    /// the data stack is not full, then store its depth to check it again when
+    /// the function returns.
+    fn guard_fn_entry(&mut self, args:usize) -> Vec<asm::Inst> {
+        let location = self.location_string();
+        let mut output = self.guard(
+            vec![
+                asm::Inst::Lea(asm::Op::Rax, asm::Op::Memory(format!("[rsp + {}]", args * 8))),
+                asm::Inst::Cmp(asm::Op::Rax, asm::Op::Memory("qword [_mem@stack_base]".to_string())),
+            ],
+            "jbe",
+            "_std@stack_underflow_msg",
+            location,
+        );
+        output.append(&mut self.guard(
+            vec![
+                asm::Inst::Mov(asm::Op::Rax, asm::Op::Memory("qword [_mem@stack_base]".to_string())),
+                asm::Inst::Sub(asm::Op::Rax, asm::Op::Immediate(DATA_STACK_SIZE)),
+                asm::Inst::Cmp(asm::Op::Rsp, asm::Op::Rax),
+            ],
+            "ja",
+            "_std@data_stack_overflow_msg",
+            location,
+        ));
+        output.append(&mut vec![
+            asm::Inst::Mov(asm::Op::Rax, asm::Op::Memory("qword [_mem@ret_ptr_idx]".to_string())),
+            asm::Inst::Lea(asm::Op::Rbx, asm::Op::Memory("[_mem@stack_depths]".to_string())),
+            asm::Inst::Mov(asm::Op::Memory("qword [rbx + rax * 8 - 8]".to_string()), asm::Op::Rsp),
+        ]);
+        output
+    }
+
+    /// Check that a function leaves exactly its return values on the data
+    /// stack, in place of its arguments.
+    fn guard_fn_exit(&mut self, args:usize, returns:usize) -> Vec<asm::Inst> {
+        let location = self.location_string();
+        self.guard(
+            vec![
+                asm::Inst::Mov(asm::Op::Rax, asm::Op::Memory("qword [_mem@ret_ptr_idx]".to_string())),
+                asm::Inst::Lea(asm::Op::Rbx, asm::Op::Memory("[_mem@stack_depths]".to_string())),
+                asm::Inst::Mov(asm::Op::Rax, asm::Op::Memory("qword [rbx + rax * 8 - 8]".to_string())),
+                asm::Inst::Add(asm::Op::Rax, asm::Op::Immediate((args as isize - returns as isize) * 8)),
+                asm::Inst::Cmp(asm::Op::Rsp, asm::Op::Rax),
+            ],
+            "je",
+            "_std@stack_mismatch_msg",
+            location,
+        )
+    }
+
+    /// A runtime check: the program stops with the `message` followed by the
+    /// location if the `check` does not end with the condition of `jump_if_ok`.
+    fn guard(&mut self, mut check:Vec<asm::Inst>, jump_if_ok:&'static str, message:&str, location:usize) -> Vec<asm::Inst> {
+        let label = format!("GUARD_{}", self.internals.guards);
+        self.internals.guards += 1;
+        check.append(&mut vec![
+            asm::Inst::Inst1Op(jump_if_ok, asm::Op::Label(label.clone())),
+            asm::Inst::Lea(asm::Op::Rcx, asm::Op::Memory(format!("[{}]", message))),
+            asm::Inst::Lea(asm::Op::Rdx, asm::Op::Memory(format!("[str_{}]", location))),
+            asm::Inst::Inst1Op("jmp", asm::Op::Label("_std@panic".to_string())),
+            asm::Inst::Label(format!("{}:", label)),
+        ]);
+        check
+    }
+
+    /// The location of the token currently compiled as a string, like
+    /// `file.rk:12:5`.
+    fn location_string(&mut self) -> usize {
+        let (row, col, filename) = self.internals.location.clone();
+        self.internals.push_string(format!("{}:{}:{}", filename, row, col))
+    }
+
     fn push_op(&mut self, op:&str) {
         self.output.push_str(op);
     }
@@ -102,7 +349,11 @@ impl Compiler {
         match self.platform {
             Platform::Windows => {
                 self.push_op(template::header());
-                self.push_op(template::ret_ptr());
+                self.push_op(&template::ret_ptr(self.max_depth));
+                self.push_op(template::panic());
+                if self.debug_runtime {
+                    self.push_op(&template::debug_runtime(self.max_depth));
+                }
                 self.push_op(template::print_int());
                 self.push_op(template::variables());
                 self.push_op(template::exit());
@@ -111,3 +362,77 @@ impl Compiler {
         self.push_op("\n");
     }
 }
+
+/// The functions called from the top level code, directly or through other
+/// functions. The other functions are never used and are not compiled.
+fn reachable_functions(tokens:&[token::Token], functions:&parser::Functions) -> HashSet<String> {
+    let mut reachable = HashSet::new();
+    let mut to_visit = called_functions(tokens, 0, tokens.len(), functions);
+    while let Some(identifier) = to_visit.pop() {
+        if reachable.insert(identifier.clone()) {
+            let (start, end, _, _, _) = functions[&identifier];
+            to_visit.append(&mut called_functions(tokens, start, end, functions));
+        }
+    }
+    reachable
+}
+
+/// The functions called by the tokens between `start` and `end`. The bodies of
+/// the declarations (`fn`, `const`, `struct` and `memory`) are skipped like when
+/// they are compiled.
+fn called_functions(tokens:&[token::Token], start:usize, end:usize, functions:&parser::Functions) -> Vec<String> {
+    let mut called = vec![];
+    let mut idx = start;
+    while idx < end {
+        match &tokens[idx].typ {
+            parser::typ::Typ::ControlFlow(control_flow::Token::Fn | control_flow::Token::Const | control_flow::Token::Struct | control_flow::Token::Memory) => {
+                if let Some(jmp_idx) = tokens[idx].jmp_idx {
+                    idx = jmp_idx;
+                }
+            },
+            parser::typ::Typ::Identifier(identifier) if functions.contains_key(*identifier) => {
+                called.push(identifier.to_string());
+            },
+            parser::typ::Typ::Memory(parser::typ::mem::Token::FnAddress(identifier)) => {
+                called.push(identifier.clone());
+            },
+            // An exported function can be called by the foreign code.
+            parser::typ::Typ::Sys(parser::typ::sys::Token::Exported(function)) => {
+                called.push(function.name.clone());
+            },
+            _ => (),
+        }
+        idx += 1;
+    }
+    called
+}
+
+/// Find the tokens that are the target of more than one jump. The end of a
+/// `fn` with a `ret` is always one because it is not compiled as a token and
+/// the arms of a `case` are always one because they can be in a jump table.
+fn shared_targets(tokens:&[token::Token]) -> HashSet<usize> {
+    let mut shared = HashSet::new();
+    let mut jumps:HashMap<usize, usize> = HashMap::new();
+    for tok in tokens.iter() {
+        if let parser::typ::Typ::ControlFlow(keyword) = &tok.typ {
+            match (keyword, tok.jmp_idx) {
+                (control_flow::Token::Ret, Some(idx)) => {
+                    shared.insert(idx);
+                },
+                (control_flow::Token::Of(arms), Some(idx)) => {
+                    shared.insert(idx);
+                    shared.extend(arms.iter().map(|(_, arm)| *arm));
+                },
+                (control_flow::Token::If | control_flow::Token::Elif
+                | control_flow::Token::Do | control_flow::Token::Else
+                | control_flow::Token::EndWhile | control_flow::Token::Break
+                | control_flow::Token::Continue | control_flow::Token::Arm, Some(idx)) => {
+                    *jumps.entry(idx).or_insert(0) += 1;
+                },
+                _ => {},
+            }
+        }
+    }
+    shared.extend(jumps.into_iter().filter(|(_, count)| *count > 1).map(|(idx, _)| idx));
+    shared
+}
@@ -7,7 +7,6 @@ segment .bss
     _io@print_char_buffer:  resb 1
     _mem@mem: resb 256
     _mem@internal: resb 256
-	_mem@ret_ptr: resb 6144
     _mem@variables: resb 24576
 
 segment .text
@@ -24,36 +23,122 @@ segment .text
     extern printf"
 }
 
-pub fn ret_ptr() -> &'static str {
-    "
-_std@ret_ptr_addr:
-	xor rax, rax
-	mov rdx, 8
-	mov ax, word [_mem@ret_ptr_idx]
-	mul rdx
-	lea rbx, [_mem@ret_ptr]
-	add rax, rbx
-	ret
+/// The return addresses of the functions are stored on their own stack, apart
+/// from the data stack. A function that is called when `max_depth` functions
+/// are already running stops the program with a stack overflow error. The name
+/// of the function is in `rcx` when `_std@store_ret_ptr` is called.
+pub fn ret_ptr(max_depth:usize) -> String {
+    format!("
+segment .bss
+    _mem@ret_ptr: resq {}
 
+segment .text
 _std@store_ret_ptr:
 	pop r15
 	pop r14
-	call _std@ret_ptr_addr
-	mov qword [rax], r14
-	inc word [_mem@ret_ptr_idx]
+	mov rax, qword [_mem@ret_ptr_idx]
+	cmp rax, {}
+	jae _std@stack_overflow
+	lea rbx, [_mem@ret_ptr]
+	mov qword [rbx + rax * 8], r14
+	inc qword [_mem@ret_ptr_idx]
 	push r15
 	ret
 
 _std@load_ret_ptr:
-    pop r15
-	dec word [_mem@ret_ptr_idx]
-	call _std@ret_ptr_addr
-	mov r14, [rax]
+	pop r15
+	dec qword [_mem@ret_ptr_idx]
+	mov rax, qword [_mem@ret_ptr_idx]
+	lea rbx, [_mem@ret_ptr]
+	mov r14, qword [rbx + rax * 8]
 	push r14
 	push r15
+	ret
+
+_std@stack_overflow:
+	mov rdx, rcx
+	lea rcx, [_std@stack_overflow_msg]
+	jmp _std@panic", max_depth, max_depth)
+}
+
+/// Stop the program after printing the message in `rcx` followed by the one in
+/// `rdx` (both are null terminated) on the stderr. The program exits with the
+/// code 1.
+pub fn panic() -> &'static str {
+    "
+_std@panic:
+	mov r12, rcx
+	mov r13, rdx
+	and rsp, -16
+	sub rsp, 48
+	mov rcx, -12
+	call GetStdHandle
+	mov r14, rax
+	mov rcx, r12
+	call _std@write_error
+	mov rcx, r13
+	call _std@write_error
+	lea rcx, [_std@new_line]
+	call _std@write_error
+	mov rcx, 1
+	call ExitProcess
+
+_std@write_error:
+	sub rsp, 40
+	mov rdx, rcx
+	xor r8, r8
+_std@write_error_len:
+	cmp byte [rdx + r8], 0
+	je _std@write_error_write
+	inc r8
+	jmp _std@write_error_len
+_std@write_error_write:
+	mov rcx, r14
+	lea r9, [_io@print_str_chars_written]
+	mov qword [rsp + 32], 0
+	call WriteConsoleA
+	add rsp, 40
 	ret"
 }
 
+/// The data used by the `--debug-runtime` checks. The data stack starts at
+/// `_mem@stack_base` and the depth of the data stack when a function is called
+/// is stored in `_mem@stack_depths`, indexed like the return addresses.
+pub fn debug_runtime(max_depth:usize) -> String {
+    format!("
+segment .bss
+    _mem@stack_base: resq 1
+    _mem@stack_depths: resq {}
+
+segment .data
+	_std@stack_underflow_msg db \"error: data stack underflow at \", 0
+	_std@data_stack_overflow_msg db \"error: data stack overflow at \", 0
+	_std@stack_mismatch_msg db \"error: wrong number of values left on the data stack at \", 0
+
+segment .text", max_depth)
+}
+
+/// The index and the end of each `for` loop, for each depth of calls (the code
+/// outside of the functions runs at the depth 0). The slots of a loop are
+/// `_mem@loop_frame` bytes apart, each depth has its own qword in them.
+pub fn loops(count:usize, max_depth:usize) -> String {
+    format!("
+segment .bss
+    _mem@loops: resq {}
+    _mem@loop_frame equ {}
+", count * 2 * (max_depth + 1), (max_depth + 1) * 8)
+}
+
+/// The data used by the templates.
+pub fn data() -> &'static str {
+    "
+segment .data
+	_mem@ret_ptr_idx dq 0
+	_std@new_line db 13, 10, 0
+	_std@stack_overflow_msg db \"error: stack overflow in fn \", 0
+"
+}
+
 pub fn print_int() -> &'static str {
     " "
 }
@@ -87,6 +172,8 @@ _std@fetch_variable:
 pub fn exit() -> &'static str {
     "
 _std@exit:
+    and rsp, -16
+    sub rsp, 32
     lea rax, [_mem@internal]
     add rax, 32
     mov rcx, qword [rax]
@@ -0,0 +1,175 @@
+use crate::compiler::err;
+use crate::formatter::{self, Piece};
+
+/// A declaration documented by the comments just above it.
+struct Item {
+    name:String,
+    /// The declaration as it is written, without the body of a function.
+    declaration:String,
+    description:Vec<String>,
+    /// The lines after `USAGE:` in the comments.
+    usage:Vec<String>,
+}
+
+/// Generate a markdown reference of the functions and the consts declared at
+/// the top level of the files (with their includes) in `input`. The comments
+/// just above a declaration are its documentation, the lines after `USAGE:` are
+/// examples. The names of the functions of a namespace (like `std::alloc`) are
+/// linked to their documentation. Fails with the errors of the tokenizer.
+pub fn generate(input:&str) -> Result<String, Vec<err::Err>> {
+    let mut files:Vec<(String, Vec<Item>)> = vec![];
+    let mut current:Option<usize> = None;
+    let mut comments:Vec<&str> = vec![];
+    let mut level:usize = 0;
+    for (line, pieces) in input.lines().zip(formatter::lex(input)?) {
+        // A file included more than once is documented once.
+        if let Some(filename) = line.trim().strip_prefix("___rk___ __rk_newfile_rk__ ___rk___ ") {
+            current = match files.iter().position(|(name, _)| name == filename) {
+                Some(_) => None,
+                None => {
+                    files.push((filename.to_string(), vec![]));
+                    Some(files.len() - 1)
+                }
+            };
+            comments.clear();
+            level = 0;
+            continue;
+        }
+        match pieces.as_slice() {
+            [] => comments.clear(),
+            [Piece::Comment(comment)] => comments.push(comment),
+            _ => {
+                if let (0, Some(file)) = (level, current) {
+                    if let Some(item) = item(&pieces, &comments) {
+                        files[file].1.push(item);
+                    }
+                }
+                comments.clear();
+                let depth = formatter::depth(&pieces);
+                level = (level + depth.opened).saturating_sub(depth.closed);
+            }
+        }
+    }
+    Ok(render(&files))
+}
+
+/// The documented declaration of a line, a function or a const. The names
+/// starting with `_` are internal, they are not documented.
+fn item(pieces:&[Piece], comments:&[&str]) -> Option<Item> {
+    let pieces:Vec<&Piece> = pieces.iter().filter(|piece| !matches!(piece, Piece::Space(_))).collect();
+    let pieces = match pieces.as_slice() {
+        [Piece::Word("export"), rest @ ..] => rest,
+        pieces => pieces,
+    };
+    let (name, declaration) = match pieces {
+        [Piece::Word("fn"), Piece::Word(name), Piece::Literal(annotation), ..] if annotation.starts_with('[') => {
+            (name.to_string(), format!("fn {}{}", name, annotation))
+        },
+        [Piece::Word("const"), Piece::Word(name), rest @ ..] => {
+            let mut declaration = format!("const {}", name);
+            for piece in rest.iter() {
+                match piece {
+                    Piece::Word(text) | Piece::Literal(text) => {
+                        declaration.push(' ');
+                        declaration.push_str(text);
+                        if *text == "end" {
+                            break;
+                        }
+                    },
+                    _ => break,
+                }
+            }
+            (name.to_string(), declaration)
+        },
+        _ => return None,
+    };
+    if name.rsplit("::").next().is_some_and(|name| name.starts_with('_')) {
+        return None;
+    }
+    let lines:Vec<&str> = comments.iter().map(|comment| {
+        let text = comment.trim_start_matches('#');
+        text.strip_prefix(' ').unwrap_or(text).trim_end()
+    }).collect();
+    // The comments of a section title (`=== ===`) are not part of the
+    // documentation.
+    let lines = match lines.iter().rposition(|line| !line.is_empty() && line.chars().all(|c| c == '=' || c == ' ')) {
+        Some(banner) => &lines[banner + 1..],
+        None => &lines[..],
+    };
+    let (description, usage) = match lines.iter().position(|line| line.trim_start().starts_with("USAGE:")) {
+        Some(idx) => {
+            let first = lines[idx].trim_start()["USAGE:".len()..].trim();
+            let usage = std::iter::once(first).chain(lines[idx + 1..].iter().map(|line| line.trim()))
+                .filter(|line| !line.is_empty())
+                .map(str::to_string)
+                .collect();
+            (&lines[..idx], usage)
+        },
+        None => (lines, vec![]),
+    };
+    Some(Item {
+        name,
+        declaration,
+        description:description.iter().map(|line| line.to_string()).collect(),
+        usage,
+    })
+}
+
+/// The id of the title of a documented declaration.
+fn anchor(name:&str) -> String {
+    name.replace("::", "-")
+}
+
+/// Link the names of the documented functions of a namespace, except `current`.
+fn link(text:&str, names:&[&str], current:&str) -> String {
+    let is_name_char = |c:char| c.is_alphanumeric() || c == '_' || c == ':';
+    let mut output = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(is_name_char) {
+        let end = rest[start..].find(|c| !is_name_char(c)).map_or(rest.len(), |len| start + len);
+        let name = rest[start..end].trim_end_matches(':');
+        let name_end = start + name.len();
+        if name.contains("::") && name != current && names.contains(&name) {
+            let link = format!("[`{}`](#{})", name, anchor(name));
+            if rest[..start].ends_with('`') && rest[name_end..].starts_with('`') {
+                output.push_str(&rest[..start - 1]);
+                output.push_str(&link);
+                rest = &rest[name_end + 1..];
+            } else {
+                output.push_str(&rest[..start]);
+                output.push_str(&link);
+                rest = &rest[name_end..];
+            }
+        } else {
+            output.push_str(&rest[..end]);
+            rest = &rest[end..];
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+fn render(files:&[(String, Vec<Item>)]) -> String {
+    let names:Vec<&str> = files.iter().flat_map(|(_, items)| items.iter().map(|item| item.name.as_str())).collect();
+    let mut output = "# Reference\n\nGenerated with `rack doc` from the comments above the functions and the consts.\n".to_string();
+    for (filename, items) in files.iter().filter(|(_, items)| !items.is_empty()) {
+        output.push_str(&format!("\n## `{}`\n\n", filename));
+        for item in items.iter() {
+            output.push_str(&format!("- [`{}`](#{})\n", item.name, anchor(&item.name)));
+        }
+        for item in items.iter() {
+            output.push_str(&format!("\n<a id=\"{}\"></a>\n\n### `{}`\n\n```\n{}\n```\n", anchor(&item.name), item.name, item.declaration));
+            let description = item.description.iter()
+                .map(|line| link(line, &names, &item.name))
+                .collect::<Vec<String>>()
+                .join("\n");
+            if !description.trim().is_empty() {
+                output.push_str(&format!("\n{}\n", description.trim()));
+            }
+            if !item.usage.is_empty() {
+                output.push_str(&format!("\nUsage:\n\n```\n{}\n```\n", item.usage.join("\n")));
+            }
+        }
+    }
+    output
+}
@@ -0,0 +1,184 @@
+use crate::compiler::err;
+use crate::parser::{token, typ};
+
+/// The indentation of one level of block.
+pub const INDENT:&str = "    ";
+
+/// A piece of a line of rack code. The pieces are the tokens of the parser, but
+/// they keep the text as it is written (with the quotes of the strings and the
+/// chars, the brackets of the type annotations and the comments).
+#[derive(Debug, PartialEq)]
+pub enum Piece<'a> {
+    /// A keyword, an identifier, a number or a punctuation (`(`, `)`, `,`).
+    Word(&'a str),
+    /// A string, a char, a type annotation or a text that is not a token (like
+    /// an empty string).
+    Literal(&'a str),
+    /// The spaces and the tabs between two pieces.
+    Space(&'a str),
+    /// A comment, from the `#` to the end of the line.
+    Comment(&'a str),
+}
+
+/// Split each line of `input` (as split by `lines()`) in pieces with the
+/// tokenizer of the parser. The text of a line, without its leading and
+/// trailing spaces, is the concatenation of its pieces.
+pub fn lex(input:&str) -> Result<Vec<Vec<Piece<'_>>>, Vec<err::Err>> {
+    let (tokens, comments) = token::tokenize_with_comments(input)?;
+    // The values of the tokens and the comments are slices of `input`.
+    let offset = |text:&str| text.as_ptr() as usize - input.as_ptr() as usize;
+    let mut spans:Vec<(usize, usize, bool)> = tokens.iter().map(|tok| {
+        let (start, end) = (offset(tok.value), offset(tok.value) + tok.value.len());
+        match tok.typ {
+            typ::Typ::Str(_) => (widen(input, start, end, "\"", "\""), true),
+            typ::Typ::Slice(_) => (widen(input, start, end, "s\"", "\""), true),
+            typ::Typ::Char(_) => (widen(input, start, end, "'", "'"), true),
+            typ::Typ::Helper(typ::helper::Token::TypeAnnot(..)) => (widen(input, start, end, "[", "]"), true),
+            _ => ((start, end), false),
+        }
+    }).map(|((start, end), is_literal)| (start, end, is_literal)).collect();
+    spans.sort_by_key(|(start, _, _)| *start);
+    let mut comments = comments.into_iter().map(|comment| (offset(comment), comment)).peekable();
+    let mut spans = spans.into_iter().peekable();
+    let mut lines = vec![];
+    for line in input.lines() {
+        let text = line.trim();
+        let mut position = offset(text);
+        let end = position + text.len();
+        let mut pieces = vec![];
+        while let Some((start, stop, is_literal)) = spans.next_if(|(start, _, _)| *start < end) {
+            let start = start.max(position);
+            push_gap(&mut pieces, &input[position..start]);
+            let text = &input[start..stop];
+            pieces.push(if is_literal { Piece::Literal(text) } else { Piece::Word(text) });
+            position = stop;
+        }
+        match comments.next_if(|(start, _)| *start < end) {
+            Some((start, comment)) => {
+                push_gap(&mut pieces, &input[position..start]);
+                pieces.push(Piece::Comment(comment));
+            },
+            None => push_gap(&mut pieces, &input[position..end]),
+        }
+        lines.push(pieces);
+    }
+    Ok(lines)
+}
+
+/// The span of a token with its delimiters (like the quotes of a string), the
+/// tokenizer trims the spaces between the delimiters and the value.
+fn widen(input:&str, start:usize, end:usize, open:&str, close:&str) -> (usize, usize) {
+    let start = input[..start].trim_end().len() - open.len();
+    let end = input.len() - input[end..].trim_start().len() + close.len();
+    (start, end)
+}
+
+/// Push the text between two tokens, its spaces and the text that is not a
+/// token.
+fn push_gap<'a>(pieces:&mut Vec<Piece<'a>>, gap:&'a str) {
+    let text = gap.trim();
+    if text.is_empty() {
+        if !gap.is_empty() {
+            pieces.push(Piece::Space(gap));
+        }
+        return;
+    }
+    let start = gap.len() - gap.trim_start().len();
+    if start > 0 {
+        pieces.push(Piece::Space(&gap[..start]));
+    }
+    pieces.push(Piece::Literal(text));
+    if start + text.len() < gap.len() {
+        pieces.push(Piece::Space(&gap[start + text.len()..]));
+    }
+}
+
+/// How a line changes the depth of the blocks. `opened` blocks are still open
+/// at the end of the line and `closed` blocks of the previous lines are closed
+/// by the line, `leading_ends` of them before any other word. `dedent` is set
+/// when the line starts with an `else`. The leading `end` and `else` are not
+/// indented like the body they close.
+pub struct Depth {
+    pub opened:usize,
+    pub closed:usize,
+    pub leading_ends:usize,
+    pub dedent:bool,
+}
+
+pub fn depth(pieces:&[Piece]) -> Depth {
+    let mut depth = Depth { opened:0, closed:0, leading_ends:0, dedent:false };
+    let mut is_leading = true;
+    let mut previous = "";
+    for piece in pieces.iter() {
+        let word = match piece {
+            Piece::Word(word) => *word,
+            Piece::Space(_) => continue,
+            _ => {
+                is_leading = false;
+                previous = "";
+                continue;
+            },
+        };
+        match word {
+            "end" => {
+                if depth.opened > 0 {
+                    depth.opened -= 1;
+                } else {
+                    depth.closed += 1;
+                    if is_leading {
+                        depth.leading_ends += 1;
+                    }
+                }
+            },
+            "else" if is_leading => {
+                depth.dedent = true;
+                is_leading = false;
+            },
+            // The `fn` of an `extern fn` has no body.
+            "fn" if previous == "extern" => is_leading = false,
+            "if" | "while" | "for" | "case" | "fn" | "const" | "struct" | "memory" | "asm" | "macro" => {
+                depth.opened += 1;
+                is_leading = false;
+            },
+            _ => is_leading = false,
+        }
+        previous = word;
+    }
+    depth
+}
+
+/// Reformat a rack source. The blocks are indented with 4 spaces for each level,
+/// the spaces between the words are reduced to one and the trailing spaces are
+/// removed. The comments, the strings, the calls (`f(a, b)` or `a b f`), the
+/// blank lines and the line endings (`\n` or `\r\n`) are kept as they are
+/// written. Fails with the errors of the tokenizer.
+pub fn format(filename:&str, source:&str) -> Result<String, Vec<err::Err>> {
+    let newline = if source.contains("\r\n") { "\r\n" } else { "\n" };
+    // The name of the file is given to the tokenizer for its errors.
+    let input = format!("___rk___ __rk_newfile_rk__ ___rk___ {}\n{}", filename, source);
+    let mut output = String::new();
+    let mut level:usize = 0;
+    for pieces in lex(&input)?.iter().skip(1) {
+        if pieces.is_empty() {
+            output.push_str(newline);
+            continue;
+        }
+        let depth = depth(pieces);
+        let indent = level.saturating_sub(depth.leading_ends + depth.dedent as usize);
+        output.push_str(&INDENT.repeat(indent));
+        for (idx, piece) in pieces.iter().enumerate() {
+            match piece {
+                // The spaces before a comment can align it with other comments.
+                Piece::Space(space) if matches!(pieces.get(idx + 1), Some(Piece::Comment(_))) => output.push_str(space),
+                Piece::Space(_) => output.push(' '),
+                Piece::Word(text) | Piece::Literal(text) | Piece::Comment(text) => output.push_str(text),
+            }
+        }
+        output.push_str(newline);
+        level = (level + depth.opened).saturating_sub(depth.closed);
+    }
+    while output.ends_with(&newline.repeat(2)) {
+        output.truncate(output.len() - newline.len());
+    }
+    Ok(output)
+}
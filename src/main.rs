@@ -2,6 +2,8 @@ mod parser;
 mod compiler;
 pub mod token;
 mod type_checker;
+mod formatter;
+mod doc;
 
 use colored::*;
 use std::fs;
@@ -13,8 +15,36 @@ use std::collections::HashMap;
 // calling conventions:
 // https://docs.microsoft.com/en-us/cpp/build/x64-calling-convention?view=msvc-160
 
+/// The flags changing how a program is compiled.
+struct Options {
+    max_depth:usize,
+    is_debug_runtime:bool,
+    is_print_unused:bool,
+    convention:compiler::abi::Convention,
+}
+
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+    if args.len() > 1 && args[1] == "fmt" {
+        args.drain(..2);
+        let is_check = flag(&mut args, "--check");
+        format(&args, is_check);
+        return;
+    }
+    if args.len() > 1 && args[1] == "doc" {
+        document(args.get(2));
+        return;
+    }
+    // `rack build <file_name>` is the same as `rack <file_name>`.
+    if args.len() > 1 && args[1] == "build" {
+        args.remove(1);
+    }
+    let options = Options {
+        max_depth:max_depth(&mut args),
+        is_debug_runtime:flag(&mut args, "--debug-runtime"),
+        is_print_unused:flag(&mut args, "--print-unused"),
+        convention:convention(&mut args),
+    };
     if args.len() == 1 {
         compiler::err::Err::command_line(
             "No file was specified to be compiled.\n\tCommand usage: `rack <file_name>.rk`".to_string(),
@@ -34,13 +64,13 @@ fn main() {
     match fs::read_to_string(filename) {
         Ok(content) => {
             input.push_str(&content);
-            input = parser::parse_includes(&filename, &input);
+            input = parser::parse_includes(filename, &input);
             let (tokens, functions, consts) = parser::parse(&input);
             let type_checker = type_checker::TypeChecker::new();
             if is_debug_stack {
                 debug_stack(type_checker, tokens, functions, consts, &args[3]);
             } else {
-                compile(filename, type_checker, tokens, functions, consts);
+                compile(filename, type_checker, tokens, functions, consts, options);
             }
         },
         Err(_) => {
@@ -54,20 +84,161 @@ fn main() {
     }
 }
 
+/// Remove the `name` flag from the arguments and return whether it was there.
+fn flag(args:&mut Vec<String>, name:&str) -> bool {
+    match args.iter().position(|arg| arg == name) {
+        Some(position) => {
+            args.remove(position);
+            true
+        },
+        None => false,
+    }
+}
+
+/// Remove the `--max-depth <depth>` flag from the arguments and return the
+/// maximum number of functions running at the same time.
+fn max_depth(args:&mut Vec<String>) -> usize {
+    let position = match args.iter().position(|arg| arg == "--max-depth") {
+        Some(position) => position,
+        None => return compiler::DEFAULT_MAX_DEPTH,
+    };
+    let depth = args.get(position + 1).and_then(|depth| depth.parse::<usize>().ok());
+    match depth {
+        Some(depth) if depth > 0 => {
+            args.drain(position..position + 2);
+            depth
+        },
+        _ => {
+            compiler::err::Err::command_line(
+                "The `--max-depth` flag expects a positive number.\n\tCommand usage: `rack <file_name>.rk --max-depth <depth>`".to_string(),
+            ).panic();
+            compiler::DEFAULT_MAX_DEPTH
+        }
+    }
+}
+
+/// Remove the `--abi <convention>` flag from the arguments and return the
+/// calling convention of the extern functions, `win64` by default.
+fn convention(args:&mut Vec<String>) -> compiler::abi::Convention {
+    let position = match args.iter().position(|arg| arg == "--abi") {
+        Some(position) => position,
+        None => return compiler::abi::Convention::Win64,
+    };
+    let convention = args.get(position + 1).and_then(|name| compiler::abi::Convention::from_name(name));
+    match convention {
+        Some(convention) => {
+            args.drain(position..position + 2);
+            convention
+        },
+        None => {
+            compiler::err::Err::command_line(
+                "The `--abi` flag expects `win64` or `sysv`.\n\tCommand usage: `rack <file_name>.rk --abi <win64|sysv>`".to_string(),
+            ).panic();
+            compiler::abi::Convention::Win64
+        }
+    }
+}
+
+/// Reformat the files in place. With `--check`, the files are not changed and
+/// the command fails if one of them is not formatted.
+fn format(filenames:&[String], is_check:bool) {
+    if filenames.is_empty() {
+        compiler::err::Err::command_line(
+            "No file was specified to be formatted.\n\tCommand usage: `rack fmt [--check] <file_name>.rk ...`".to_string(),
+        ).panic();
+    }
+    let mut unformatted = 0;
+    for filename in filenames.iter() {
+        let source = match fs::read_to_string(filename) {
+            Ok(source) => source,
+            Err(_) => {
+                compiler::err::Err::command_line(
+                    format!(
+                        "The file `{}` does not exists or is not able to being opened. Check the path and permission of the file.",
+                        filename,
+                    )
+                ).panic();
+                continue;
+            }
+        };
+        let formatted = match formatter::format(filename, &source) {
+            Ok(formatted) => formatted,
+            Err(errors) => {
+                errors.iter().for_each(|error| error.print());
+                std::process::exit(1);
+            }
+        };
+        if formatted == source {
+            continue;
+        }
+        if is_check {
+            println!("{} {}", "unformatted".yellow().bold(), filename);
+            unformatted += 1;
+        } else {
+            fs::write(filename, formatted).unwrap();
+            println!("{} {}", "formatted".green().bold(), filename);
+        }
+    }
+    if unformatted > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// Write the reference of the file and of its includes in `doc.md`.
+fn document(filename:Option<&String>) {
+    let filename = match filename {
+        Some(filename) => filename,
+        None => {
+            compiler::err::Err::command_line(
+                "No file was specified to be documented.\n\tCommand usage: `rack doc <file_name>.rk`".to_string(),
+            ).panic();
+            return;
+        }
+    };
+    match fs::read_to_string(filename) {
+        Ok(content) => {
+            let input = format!("___rk___ __rk_newfile_rk__ ___rk___ {}\n{}", filename, content);
+            let input = parser::parse_includes(filename, &input);
+            let reference = match doc::generate(&input) {
+                Ok(reference) => reference,
+                Err(errors) => {
+                    errors.iter().for_each(|error| error.print());
+                    std::process::exit(1);
+                }
+            };
+            fs::write("doc.md", reference).unwrap();
+            println!("{} {} in doc.md", "documented".green().bold(), filename);
+        },
+        Err(_) => {
+            compiler::err::Err::command_line(
+                format!(
+                    "The file `{}` does not exists or is not able to being opened. Check the path and permission of the file.",
+                    filename,
+                )
+            ).panic();
+        }
+    }
+}
+
 fn compile(
     filename:&String,
     mut type_checker:type_checker::TypeChecker,
-    tokens:Vec<parser::token::Token>,
-    functions:HashMap<String, (usize, usize, Vec<Vec<type_checker::Typ>>, Vec<Vec<type_checker::Typ>>, bool)>,
-    consts:HashMap<String, isize>,
+    mut tokens:Vec<parser::token::Token>,
+    functions:parser::Functions,
+    consts:HashMap<String, parser::constant::Value>,
+    options:Options,
 ) {
     println!(
         "{} {}",
         "compiling".green().bold(),
         filename,
     );
-    type_checker.checks(&tokens, &functions, &consts);
+    type_checker.checks(&mut tokens, &functions, &consts);
     let mut compiler = compiler::Compiler::new(functions, consts);
+    compiler.max_depth = options.max_depth;
+    compiler.debug_runtime = options.is_debug_runtime;
+    compiler.print_unused = options.is_print_unused;
+    compiler.convention = options.convention;
     compiler.compile(tokens);
     println!(
         "{} {}",
@@ -78,13 +249,13 @@ fn compile(
 
 fn debug_stack(
     mut type_checker:type_checker::TypeChecker,
-    tokens:Vec<parser::token::Token>,
-    functions:HashMap<String, (usize, usize, Vec<Vec<type_checker::Typ>>, Vec<Vec<type_checker::Typ>>, bool)>,
-    consts:HashMap<String, isize>,
+    mut tokens:Vec<parser::token::Token>,
+    functions:parser::Functions,
+    consts:HashMap<String, parser::constant::Value>,
     function_to_debug:&String,
 ) {
     match functions.get(function_to_debug) {
-        Some((start, end, stack, _, _)) => {
+        Some((start, end, stack, returns, _)) => {
             let args: Vec<type_checker::Typ> = stack.iter().rev().map(|arg| {
                 if arg.len() > 1 {
                     type_checker::Typ::Any
@@ -93,7 +264,8 @@ fn debug_stack(
                 }
             }).collect();
             type_checker.stack = args;
-            type_checker.check(&tokens, &functions, &consts, *start, *end, true);
+            type_checker.returns = returns.clone();
+            type_checker.check(&mut tokens, &functions, &consts, *start, *end, true);
         }
         _ => {
             compiler::err::Err::command_line(
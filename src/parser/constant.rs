@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use crate::compiler::{asm, err, internals};
+use crate::parser::{token, typ};
+use crate::type_checker;
+
+/// The value of a `const`, computed at compile time.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Value {
+    Int(isize),
+    Char(char),
+    Float(f64),
+    Str(String),
+}
+
+impl Value {
+    pub fn typ(&self) -> type_checker::Typ {
+        match self {
+            Value::Int(_) => type_checker::Typ::Int,
+            Value::Char(_) => type_checker::Typ::Char,
+            Value::Float(_) => type_checker::Typ::Float,
+            Value::Str(_) => type_checker::Typ::Str,
+        }
+    }
+
+    /// Push the value on the stack, like the literal it was computed from.
+    pub fn compile(&self, internals:&mut internals::Internals) -> Vec<asm::Inst> {
+        let value = match self {
+            Value::Int(integer) => asm::Op::Immediate(*integer),
+            Value::Char(c) => asm::Op::Immediate(*c as isize),
+            Value::Float(float) => asm::Op::Immediate(float.to_bits() as isize),
+            Value::Str(string) => {
+                let str_idx = internals.push_string(string.to_string());
+                return vec![
+                    asm::Inst::Lea(asm::Op::Rax, asm::Op::Memory(format!("[str_{}]", str_idx))),
+                    asm::Inst::Push(asm::Op::Rax)
+                ];
+            }
+        };
+        vec![
+            asm::Inst::Mov(asm::Op::Rax, value),
+            asm::Inst::Push(asm::Op::Rax)
+        ]
+    }
+}
+
+/// Evaluate the body of a `const` as a small stack program. Only literals,
+/// earlier consts, arithmetic, comparisons, casts and the stack operations
+/// (`dup`, `drop`, `swap`, `over` and `rot`) can be used. The body must leave
+/// exactly one value on the stack. `end` is the token closing the body, it is
+/// used to report errors about the final stack.
+pub fn eval(tokens:&[token::Token], end:&token::Token, consts:&HashMap<String, Value>) -> Result<Value, err::Err> {
+    let mut stack:Vec<Value> = vec![];
+    for tok in tokens.iter() {
+        let error = |message:String| {
+            err::Err::new(message, (tok.row, tok.col, tok.filename.to_string()), tok.len())
+        };
+        let underflow = || error("Not enough values on the stack for this operation in the `const`.".to_string());
+        match &tok.typ {
+            typ::Typ::Int(integer) => stack.push(Value::Int(*integer)),
+            typ::Typ::Char(c) => stack.push(Value::Char(*c)),
+            typ::Typ::Float(float) => stack.push(Value::Float(*float)),
+            typ::Typ::Str(string) => stack.push(Value::Str(string.to_string())),
+            typ::Typ::Identifier(identifier) => {
+                match consts.get(*identifier) {
+                    Some(value) => stack.push(value.clone()),
+                    None => return Err(error(format!("`{}` is not a const declared before this one.", identifier))),
+                }
+            },
+            typ::Typ::Arithmetic(op) => {
+                let rhs = stack.pop().ok_or_else(underflow)?;
+                let lhs = stack.pop().ok_or_else(underflow)?;
+                let value = match (lhs, rhs) {
+                    (Value::Int(lhs), Value::Int(rhs)) => op.eval(lhs, rhs).map(Value::Int),
+                    (Value::Float(lhs), Value::Float(rhs)) => op.eval_float(lhs, rhs).map(Value::Float),
+                    (Value::Char(lhs), Value::Int(rhs)) if matches!(op, typ::arith::Token::Plus | typ::arith::Token::Minus) => {
+                        op.eval(lhs as isize, rhs).map(|c| Value::Char(((c & 0xFF) as u8) as char))
+                    },
+                    (Value::Char(lhs), Value::Char(rhs)) if *op == typ::arith::Token::Minus => {
+                        op.eval(lhs as isize, rhs as isize).map(Value::Int)
+                    },
+                    (lhs, rhs) => Err(format!("This arithmetic operation cannot be used with `{}` and `{}` in a `const`.", lhs.typ(), rhs.typ())),
+                };
+                stack.push(value.map_err(error)?);
+            },
+            typ::Typ::Comparison(op) => {
+                let rhs = stack.pop().ok_or_else(underflow)?;
+                let lhs = stack.pop().ok_or_else(underflow)?;
+                let value = match (lhs, rhs) {
+                    (Value::Int(lhs), Value::Int(rhs)) => op.eval(lhs, rhs),
+                    (Value::Char(lhs), Value::Char(rhs)) => op.eval(lhs, rhs),
+                    (Value::Float(lhs), Value::Float(rhs)) => op.eval(lhs, rhs),
+                    (lhs, rhs) => return Err(error(format!("`{}` and `{}` cannot be compared in a `const`.", lhs.typ(), rhs.typ()))),
+                };
+                stack.push(Value::Int(value));
+            },
+            typ::Typ::Cast(cast) => {
+                let value = match (cast, stack.pop().ok_or_else(underflow)?) {
+                    (typ::cast::Token::IntToFloat, Value::Int(integer)) => Value::Float(integer as f64),
+                    (typ::cast::Token::FloatToInt, Value::Float(float)) => Value::Int(float as isize),
+                    (typ::cast::Token::IntToChar, Value::Int(integer)) => Value::Char(((integer & 0xFF) as u8) as char),
+                    (typ::cast::Token::CharToInt, Value::Char(c)) => Value::Int(c as isize),
+                    (cast, value) => return Err(error(format!("`{}` cannot be used on a value of type `{}`.", cast, value.typ()))),
+                };
+                stack.push(value);
+            },
+            typ::Typ::Memory(typ::mem::Token::Dup) => {
+                let value = stack.last().ok_or_else(underflow)?.clone();
+                stack.push(value);
+            },
+            typ::Typ::Memory(typ::mem::Token::Drop) => {
+                stack.pop().ok_or_else(underflow)?;
+            },
+            typ::Typ::Memory(typ::mem::Token::Swap) => {
+                let a = stack.pop().ok_or_else(underflow)?;
+                let b = stack.pop().ok_or_else(underflow)?;
+                stack.push(a);
+                stack.push(b);
+            },
+            typ::Typ::Memory(typ::mem::Token::Over) => {
+                if stack.len() < 2 {
+                    return Err(underflow());
+                }
+                stack.push(stack[stack.len() - 2].clone());
+            },
+            typ::Typ::Memory(typ::mem::Token::Rot) => {
+                if stack.len() < 3 {
+                    return Err(underflow());
+                }
+                let value = stack.remove(stack.len() - 3);
+                stack.push(value);
+            },
+            typ::Typ::Ignore | typ::Typ::Helper(_) => {},
+            _ => return Err(error("Only literals, consts, arithmetic, comparisons, casts and stack operations (`dup`, `drop`, `swap`, `over` and `rot`) can be used in a `const`.".to_string())),
+        }
+    }
+    match stack.as_slice() {
+        [value] => Ok(value.clone()),
+        _ => {
+            Err(err::Err::new(
+                format!("The body of a `const` must leave exactly one value on the stack, {} found.", stack.len()),
+                (end.row, end.col, end.filename.to_string()), end.len()
+            ))
+        }
+    }
+}
@@ -1,12 +1,18 @@
+pub mod constant;
 pub mod token;
 pub mod typ;
-use crate::token::{control_flow, sys, helper};
+use crate::token::{control_flow, sys, helper, mem};
 use crate::compiler::err;
 use crate::type_checker;
 
 use std::fs;
 use std::collections::HashMap;
 
+/// Functions found while parsing, by identifier. Each entry is the index of the
+/// first token of the body, the index of the `end` token, the arguments types,
+/// the return types and if the returned values should not be checked.
+pub type Functions = HashMap<String, (usize, usize, Vec<Vec<type_checker::Typ>>, Vec<Vec<type_checker::Typ>>, bool)>;
+
 /// Check for the `include` keyword at the begining of each file. When something
 /// else is encountered (except for commented line), we stop checking for the
 /// include keyword (it can only be at the top of the file).
@@ -26,15 +32,15 @@ pub fn parse_includes(main_file:&str, input:&str) -> String {
         if !line.trim().starts_with("include") 
             && !line.trim().starts_with("___rk___") 
             && !line.trim().starts_with("#")
-            && line.trim().len() > 0 {
+            && !line.trim().is_empty() {
             can_include = false;
             continue;
         }
-        if line.trim().starts_with("___rk___") || line.trim().starts_with("#") || line.trim().len() == 0 {
+        if line.trim().starts_with("___rk___") || line.trim().starts_with("#") || line.trim().is_empty() {
             continue;
         }
         let values = line.trim().split("include").collect::<Vec<&str>>();
-        if (values.len() != 2 && line.trim().starts_with("include")) || values[1].trim().len() == 0 {
+        if (values.len() != 2 && line.trim().starts_with("include")) || values[1].trim().is_empty() {
             let message = match values.len() {
                 1 | 2 => "You must put a string after the `include` keyword to include an other file. Example : `include \"std.rk\"".to_string(),
                 tok_count => format!("You can only include one file per `include` keyword but {} tokens were found after the `include` keyword.", tok_count)
@@ -91,15 +97,15 @@ pub fn parse_includes(main_file:&str, input:&str) -> String {
             ).panic();
         }
     }
-    content.push_str("\n");
+    content.push('\n');
     content.push_str(input);
     content
 }
 
 pub fn parse<'a>(input:&'a str) -> (
     Vec<token::Token<'a>>,
-    HashMap<String, (usize, usize, Vec<Vec<type_checker::Typ>>, Vec<Vec<type_checker::Typ>>, bool)>,
-    HashMap<String, isize>
+    Functions,
+    HashMap<String, constant::Value>
 ) {
     let mut errors = vec![];
     let mut tokens = match token::tokenize(input) {
@@ -115,11 +121,40 @@ pub fn parse<'a>(input:&'a str) -> (
     let mut args_stack = vec![];
     let mut stack:Vec<(usize, control_flow::Token)> = vec![];
     let mut macros:HashMap<String, (usize, usize)> = HashMap::new();
-    let mut functions:HashMap<String, (usize, usize, Vec<Vec<type_checker::Typ>>, Vec<Vec<type_checker::Typ>>, bool)> = HashMap::new();
-    let mut consts:HashMap<String, isize> = HashMap::new();
+    let mut functions:Functions = HashMap::new();
+    let mut consts:HashMap<String, constant::Value> = HashMap::new();
+    let mut structs:Vec<String> = vec![];
+    // The name of each memory and the index of its identifier.
+    let mut memories:Vec<(String, usize)> = vec![];
+    // The `ret`, `break` and `continue` waiting for the `end` of the `fn` or
+    // the `do` they leave.
+    let mut exits:Vec<(usize, usize)> = vec![];
+    // The `else` closing the branch of an `elif`, the `end` of the chain also
+    // closes the `else` before the `elif`.
+    let mut elif_elses:Vec<usize> = vec![];
+    // The arms (value and index of the `=>`) and the `else` of each opened `case`.
+    let mut cases:Vec<(control_flow::Arms, Option<usize>)> = vec![];
+    let mut fields:HashMap<String, mem::Field> = HashMap::new();
+    // The index of the token of each field, to check their types once every
+    // struct is known.
+    let mut field_tokens:Vec<(usize, String, type_checker::Typ)> = vec![];
+    let mut externs:HashMap<String, sys::Extern> = HashMap::new();
+    // The index of the identifier of each extern function, to check the types
+    // of its signature once every struct is known.
+    let mut extern_tokens:Vec<(usize, sys::Extern)> = vec![];
+    let mut exports:Vec<String> = vec![];
+    // The index of the type annotation of each `asm` block, to check its types
+    // once every struct is known.
+    let mut asm_tokens:Vec<(usize, sys::Asm)> = vec![];
     for idx in 0..tokens.len() {
         if let typ::Typ::Helper(helper) = &tokens[idx].typ {
             match helper {
+                // Inside a `const` or a `memory`, parenthesis only group the
+                // statements.
+                helper::Token::ArgOpen | helper::Token::ArgClose | helper::Token::ArgSep
+                    if matches!(stack.last(), Some((_, control_flow::Token::Const | control_flow::Token::Memory))) => {
+                    tokens[idx].typ = typ::Typ::Ignore;
+                },
                 helper::Token::ArgOpen => {
                     if idx > 0 {
                         args_stack.push(idx);
@@ -166,6 +201,26 @@ pub fn parse<'a>(input:&'a str) -> (
                             tokens[op_idx].jmp_idx = Some(idx);
                             stack.push((idx, control_flow::Token::Else));
                         },
+                        Some((op_idx, control_flow::Token::Elif)) => {
+                            tokens[op_idx].jmp_idx = Some(idx);
+                            elif_elses.push(idx);
+                            stack.push((idx, control_flow::Token::Else));
+                        },
+                        // The default arm of a `case`.
+                        Some((op_idx, control_flow::Token::Of(_))) => {
+                            if let Some((_, default)) = cases.last_mut() {
+                                if default.is_some() {
+                                    errors.push(
+                                        err::Err::new(
+                                            "A `case` can only have one `else`.".to_string(),
+                                            (tokens[idx].row, tokens[idx].col, tokens[idx].filename.to_string()), tokens[idx].len()
+                                        )
+                                    )
+                                }
+                                *default = Some(idx);
+                            }
+                            stack.push((op_idx, control_flow::Token::Of(vec![])));
+                        },
                         _ => {
                             errors.push(
                                 err::Err::new(
@@ -179,12 +234,40 @@ pub fn parse<'a>(input:&'a str) -> (
                 control_flow::Token::End => {
                     match stack.pop() {
                         Some((op_idx, control_flow::Token::If))
-                        | Some((op_idx, control_flow::Token::Else))  => {
+                        | Some((op_idx, control_flow::Token::Else))
+                        | Some((op_idx, control_flow::Token::Elif)) => {
                             tokens[op_idx].jmp_idx = Some(idx);
+                            let is_chained = tokens[op_idx].typ == typ::Typ::ControlFlow(control_flow::Token::Elif)
+                                || elif_elses.contains(&op_idx);
+                            close_elif_chain(&mut tokens, &mut stack, &elif_elses, is_chained, idx);
+                        },
+                        Some((op_idx, control_flow::Token::Of(_))) => {
+                            let (arms, default) = cases.pop().unwrap_or_default();
+                            // Every arm but the first one ends the previous arm.
+                            for (_, arm_idx) in arms.iter().skip(1) {
+                                tokens[*arm_idx].jmp_idx = Some(idx);
+                            }
+                            if let Some(else_idx) = default {
+                                tokens[else_idx].jmp_idx = Some(idx);
+                            }
+                            tokens[op_idx].jmp_idx = Some(default.unwrap_or(idx));
+                            tokens[op_idx].typ = typ::Typ::ControlFlow(control_flow::Token::Of(arms));
                         },
                         Some((op_idx, control_flow::Token::Do)) => {
                             match stack.pop() {
-                                Some((while_idx, control_flow::Token::While)) => {
+                                Some((while_idx, control_flow::Token::While))
+                                | Some((while_idx, control_flow::Token::As)) => {
+                                    if tokens[while_idx].typ == typ::Typ::ControlFlow(control_flow::Token::As) {
+                                        if let Err(error) = bind_loop_index(&mut tokens[while_idx..idx], while_idx) {
+                                            errors.push(error);
+                                        }
+                                    }
+                                    for (exit_idx, _) in exits.iter().filter(|(_, opening)| *opening == op_idx) {
+                                        tokens[*exit_idx].jmp_idx = match tokens[*exit_idx].typ {
+                                            typ::Typ::ControlFlow(control_flow::Token::Continue) => Some(while_idx),
+                                            _ => Some(idx),
+                                        };
+                                    }
                                     tokens[idx].jmp_idx = Some(while_idx);
                                     tokens[idx].typ = typ::Typ::ControlFlow(control_flow::Token::EndWhile);
                                     tokens[op_idx].jmp_idx = Some(idx);
@@ -192,7 +275,7 @@ pub fn parse<'a>(input:&'a str) -> (
                                 _ => {
                                     errors.push(
                                         err::Err::new(
-                                            "Missing a `while` statement before the `do`. Should be in this format: `while <condition> do <if_true> end`".to_string(),
+                                            "Missing a `while` or a `for` statement before the `do`. Should be in this format: `while <condition> do <if_true> end`".to_string(),
                                             (tokens[idx].row, tokens[idx].col, tokens[idx].filename.to_string()), tokens[idx].len()
                                         )
                                     )
@@ -215,6 +298,9 @@ pub fn parse<'a>(input:&'a str) -> (
                             if let typ::Typ::Identifier(identifier) = tokens[op_idx + 1].typ {
                                 if let typ::Typ::Helper(helper::Token::TypeAnnot(args_type, ret_type, ignore_return)) = tokens[op_idx + 2].typ.clone() {
                                     tokens[op_idx].jmp_idx = Some(idx);
+                                    for (exit_idx, _) in exits.iter().filter(|(_, opening)| *opening == op_idx) {
+                                        tokens[*exit_idx].jmp_idx = Some(idx);
+                                    }
                                     functions.insert(identifier.to_string(), (op_idx + 3, idx, args_type.clone(), ret_type.clone(), ignore_return));
                                 } else {
                                     errors.push(
@@ -236,29 +322,74 @@ pub fn parse<'a>(input:&'a str) -> (
                         Some((op_idx, control_flow::Token::Const)) => {
                             if let typ::Typ::Identifier(identifier) = tokens[op_idx + 1].typ {
                                 tokens[op_idx].jmp_idx = Some(idx);
-                                if let typ::Typ::Int(integer) = tokens[op_idx + 2].typ {
-                                    consts.insert(identifier.to_string(), integer);
-                                } else {
-                                    errors.push(
-                                        err::Err::new(
-                                            "A `const` can only be of type `int`.".to_string(),
-                                            (tokens[idx].row, tokens[idx].col, tokens[idx].filename.to_string()), tokens[idx].len()
-                                        )
-                                    )
+                                match constant::eval(&tokens[op_idx + 2..idx], &tokens[idx], &consts) {
+                                    Ok(value) => {
+                                        consts.insert(identifier.to_string(), value);
+                                    },
+                                    Err(error) => errors.push(error),
                                 }
                             } else {
                                 errors.push(
                                     err::Err::new(
-                                        "Missing an `identifier` just after the `const` keyword. Should be in this format: `const <identifier> <int> end`".to_string(),
+                                        "Missing an `identifier` just after the `const` keyword. Should be in this format: `const <identifier> <statements> end`".to_string(),
                                         (tokens[idx].row, tokens[idx].col, tokens[idx].filename.to_string()), tokens[idx].len()
                                     )
                                 )
                             }
                         },
+                        Some((op_idx, control_flow::Token::Struct)) => {
+                            tokens[op_idx].jmp_idx = Some(idx);
+                            match parse_struct(&tokens[op_idx..idx]) {
+                                Ok((identifier, struct_fields, size)) => {
+                                    for (field_idx, field) in struct_fields.into_iter().enumerate() {
+                                        field_tokens.push((op_idx + 2 + field_idx, field.name.clone(), field.typ.clone()));
+                                        consts.insert(field.name.clone(), constant::Value::Int(field.offset as isize));
+                                        fields.insert(field.name.clone(), field);
+                                    }
+                                    consts.insert(format!("{}.size", identifier), constant::Value::Int(size as isize));
+                                    structs.push(identifier);
+                                },
+                                Err(error) => errors.push(error),
+                            }
+                        },
+                        Some((op_idx, control_flow::Token::Memory)) => {
+                            match parse_memory(&tokens[op_idx..=idx], &consts) {
+                                Ok((identifier, size)) => {
+                                    if memories.iter().any(|(name, _)| name == &identifier) {
+                                        errors.push(
+                                            err::Err::new(
+                                                format!("`{}` is already declared.", identifier),
+                                                (tokens[op_idx + 1].row, tokens[op_idx + 1].col, tokens[op_idx + 1].filename.to_string()), tokens[op_idx + 1].len()
+                                            )
+                                        )
+                                    }
+                                    // The declaration itself reserves the memory, its
+                                    // body was only needed to compute the size.
+                                    tokens[op_idx].typ = typ::Typ::Memory(mem::Token::Reserve(identifier.clone(), size));
+                                    for tok in tokens[op_idx + 1..=idx].iter_mut() {
+                                        tok.typ = typ::Typ::Ignore;
+                                    }
+                                    memories.push((identifier, op_idx + 1));
+                                },
+                                Err(error) => errors.push(error),
+                            }
+                        },
+                        Some((op_idx, control_flow::Token::Asm)) => {
+                            match parse_asm(&tokens[op_idx..=idx]) {
+                                Ok(block) => {
+                                    asm_tokens.push((op_idx + 1, block.clone()));
+                                    tokens[op_idx].typ = typ::Typ::Sys(sys::Token::Asm(block));
+                                    for tok in tokens[op_idx + 1..=idx].iter_mut() {
+                                        tok.typ = typ::Typ::Ignore;
+                                    }
+                                },
+                                Err(error) => errors.push(error),
+                            }
+                        },
                         _ => {
                             errors.push(
                                 err::Err::new(
-                                    "The `end` keyword did not match any opening statement (like `if`, `while`, `const` or `fn`).".to_string(),
+                                    "The `end` keyword did not match any opening statement (like `if`, `while`, `case`, `const`, `struct`, `memory`, `asm` or `fn`).".to_string(),
                                     (tokens[idx].row, tokens[idx].col, tokens[idx].filename.to_string()), tokens[idx].len()
                                 )
                             )
@@ -267,18 +398,293 @@ pub fn parse<'a>(input:&'a str) -> (
                     }
                 },
                 control_flow::Token::If | control_flow::Token::While | control_flow::Token::Do
-                | control_flow::Token::Fn | control_flow::Token::Const => {
+                | control_flow::Token::Fn | control_flow::Token::Const
+                | control_flow::Token::Struct | control_flow::Token::Memory
+                | control_flow::Token::Asm => {
                     stack.push((idx, keyword.clone()));
                 },
+                control_flow::Token::For => stack.push((idx, keyword.clone())),
+                // `as` ends the range of a `for` loop, it is followed by the
+                // name of the index and by `do`.
+                control_flow::Token::As => {
+                    let is_valid = matches!(tokens.get(idx + 1).map(|tok| &tok.typ), Some(typ::Typ::Identifier(_)))
+                        && matches!(tokens.get(idx + 2).map(|tok| &tok.typ), Some(typ::Typ::ControlFlow(control_flow::Token::Do)));
+                    match stack.pop() {
+                        Some((_, control_flow::Token::For)) if is_valid => stack.push((idx, keyword.clone())),
+                        _ => {
+                            errors.push(
+                                err::Err::new(
+                                    "A `for` loop should be in this format: `for <from> <to> as <identifier> do <body> end`".to_string(),
+                                    (tokens[idx].row, tokens[idx].col, tokens[idx].filename.to_string()), tokens[idx].len()
+                                )
+                            )
+                        }
+                    }
+                },
+                control_flow::Token::Elif => {
+                    if let Some((_, control_flow::Token::Else)) = stack.last() {
+                        stack.push((idx, keyword.clone()));
+                    } else {
+                        errors.push(
+                            err::Err::new(
+                                "Missing an `else` before the `elif`. Should be in this format: `<condition> if <body> else <condition> elif <body> else <body> end`".to_string(),
+                                (tokens[idx].row, tokens[idx].col, tokens[idx].filename.to_string()), tokens[idx].len()
+                            )
+                        )
+                    }
+                },
+                control_flow::Token::Case => stack.push((idx, keyword.clone())),
+                control_flow::Token::Of(_) => {
+                    if let Some((_, control_flow::Token::Case)) = stack.pop() {
+                        stack.push((idx, keyword.clone()));
+                        cases.push((vec![], None));
+                    } else {
+                        errors.push(
+                            err::Err::new(
+                                "Missing a `case` before the `of`. Should be in this format: `case <value> of <value> => <body> ... else <body> end`".to_string(),
+                                (tokens[idx].row, tokens[idx].col, tokens[idx].filename.to_string()), tokens[idx].len()
+                            )
+                        )
+                    }
+                },
+                control_flow::Token::Arm => {
+                    match (stack.last(), cases.last_mut()) {
+                        (Some((of_idx, control_flow::Token::Of(_))), Some((arms, None))) => {
+                            let arm = match arm_value(&tokens[idx - 1], &consts) {
+                                Some(_) if arms.is_empty() && idx != of_idx + 2 => Err("The first arm of a `case` must be just after the `of`."),
+                                Some(value) if arms.iter().any(|(arm_value, _)| *arm_value == value) => Err("An other arm of the `case` already has this value."),
+                                Some(value) => Ok(value),
+                                None => Err("The value of an arm must be an `int` or a `char` literal or const, just before the `=>`."),
+                            };
+                            match arm {
+                                Ok(value) => {
+                                    arms.push((value, idx));
+                                    tokens[idx - 1].typ = typ::Typ::Ignore;
+                                },
+                                Err(message) => {
+                                    errors.push(
+                                        err::Err::new(
+                                            message.to_string(),
+                                            (tokens[idx - 1].row, tokens[idx - 1].col, tokens[idx - 1].filename.to_string()), tokens[idx - 1].len()
+                                        )
+                                    )
+                                }
+                            }
+                        },
+                        _ => {
+                            errors.push(
+                                err::Err::new(
+                                    "An arm (`=>`) can only be used in a `case`, before its `else`. Should be in this format: `case <value> of <value> => <body> ... else <body> end`".to_string(),
+                                    (tokens[idx].row, tokens[idx].col, tokens[idx].filename.to_string()), tokens[idx].len()
+                                )
+                            )
+                        }
+                    }
+                },
+                control_flow::Token::Ret => {
+                    match stack.iter().rev().find(|(_, opening)| opening == &control_flow::Token::Fn) {
+                        Some((fn_idx, _)) => exits.push((idx, *fn_idx)),
+                        None => {
+                            errors.push(
+                                err::Err::new(
+                                    "`ret` can only be used inside a `fn`.".to_string(),
+                                    (tokens[idx].row, tokens[idx].col, tokens[idx].filename.to_string()), tokens[idx].len()
+                                )
+                            )
+                        }
+                    }
+                },
+                control_flow::Token::Break | control_flow::Token::Continue => {
+                    // Only the body of the innermost loop can be left, not its
+                    // condition nor the `fn` around it.
+                    let opening = stack.iter().rev().find(|(_, opening)| {
+                        matches!(opening, control_flow::Token::Do | control_flow::Token::While
+                            | control_flow::Token::For | control_flow::Token::As | control_flow::Token::Fn)
+                    });
+                    match opening {
+                        Some((do_idx, control_flow::Token::Do)) => exits.push((idx, *do_idx)),
+                        _ => {
+                            errors.push(
+                                err::Err::new(
+                                    format!("`{}` can only be used in the body of a `while` loop (between `do` and `end`).", keyword),
+                                    (tokens[idx].row, tokens[idx].col, tokens[idx].filename.to_string()), tokens[idx].len()
+                                )
+                            )
+                        }
+                    }
+                },
                 _ => {}
             }
         } else  if let typ::Typ::Sys(sys::Token::Include) = &tokens[idx].typ {
             tokens[idx].typ = typ::Typ::Ignore;
             tokens[idx + 1].typ = typ::Typ::Ignore;
+        } else if let typ::Typ::Sys(sys::Token::Extern) = &tokens[idx].typ {
+            match parse_native_signature(&tokens[idx..]) {
+                Ok(function) => {
+                    if externs.contains_key(&function.name) {
+                        errors.push(
+                            err::Err::new(
+                                format!("The extern function `{}` is already declared.", function.name),
+                                (tokens[idx + 2].row, tokens[idx + 2].col, tokens[idx + 2].filename.to_string()), tokens[idx + 2].len()
+                            )
+                        )
+                    }
+                    extern_tokens.push((idx + 2, function.clone()));
+                    externs.insert(function.name.clone(), function);
+                    for tok in tokens[idx..idx + 4].iter_mut() {
+                        tok.typ = typ::Typ::Ignore;
+                    }
+                },
+                Err(error) => errors.push(error),
+            }
+        } else if let typ::Typ::Sys(sys::Token::Export) = &tokens[idx].typ {
+            // The function itself is parsed like any other `fn`.
+            match parse_native_signature(&tokens[idx..]) {
+                Ok(function) => {
+                    exports.push(function.name.clone());
+                    tokens[idx].typ = typ::Typ::Sys(sys::Token::Exported(function));
+                },
+                Err(error) => errors.push(error),
+            }
         }
     }
 
-    if args_stack.len() > 0 {
+    // Functions can be used before their declaration.
+    for tok in tokens.iter() {
+        if let typ::Typ::Memory(mem::Token::FnAddress(name)) = &tok.typ {
+            if !functions.contains_key(name) {
+                errors.push(
+                    err::Err::new(
+                        format!("`&{}` needs a function but `{}` is not a declared function.", name, name),
+                        (tok.row, tok.col, tok.filename.to_string()), tok.len()
+                    )
+                )
+            }
+        }
+    }
+
+    // Extern functions can be called before their declaration.
+    for tok in tokens.iter_mut() {
+        if let typ::Typ::Sys(sys::Token::Sys(name)) = &tok.typ {
+            match externs.get(name) {
+                Some(function) => tok.typ = typ::Typ::Sys(sys::Token::Call(function.clone())),
+                None => {
+                    errors.push(
+                        err::Err::new(
+                            format!("`sys::{}` is not declared. Declare it with `extern fn {}[<type_annotation>]`.", name, name),
+                            (tok.row, tok.col, tok.filename.to_string()), tok.len()
+                        )
+                    )
+                }
+            }
+        }
+    }
+
+    // A memory is used by its name alone so it cannot share it with a function,
+    // a const or a variable.
+    let variables:Vec<&str> = tokens.windows(2).filter_map(|toks| match toks[0].typ {
+        typ::Typ::Identifier(identifier) if toks[1].typ == typ::Typ::Memory(mem::Token::Put) => Some(identifier),
+        _ => None,
+    }).collect();
+    for (identifier, idx) in memories.iter() {
+        let kind = if functions.contains_key(identifier) {
+            "function"
+        } else if consts.contains_key(identifier) {
+            "const"
+        } else if variables.contains(&identifier.as_str()) {
+            "variable"
+        } else {
+            continue
+        };
+        errors.push(
+            err::Err::new(
+                format!("The memory `{}` has the same name as a {}.", identifier, kind),
+                (tokens[*idx].row, tokens[*idx].col, tokens[*idx].filename.to_string()), tokens[*idx].len()
+            )
+        )
+    }
+
+    // Structs and memories can be used before their declaration, so their
+    // accessors are only resolved once every struct and memory is known.
+    // `Name.field!` is tokenized as the `Name.field` identifier followed by a
+    // fetch.
+    for idx in 0..tokens.len() {
+        if let typ::Typ::Identifier(identifier) = tokens[idx].typ {
+            let is_variable = tokens.get(idx + 1).is_some_and(|tok| tok.typ.is_affected_by_identifier());
+            if !is_variable && memories.iter().any(|(name, _)| name == identifier) {
+                tokens[idx].typ = typ::Typ::Memory(mem::Token::Static(identifier.to_string()));
+            } else if let Some(name) = identifier.strip_suffix(".export").filter(|name| exports.iter().any(|export| export == name)) {
+                tokens[idx].typ = typ::Typ::Sys(sys::Token::ExportAddress(name.to_string()));
+            } else if let Some(field) = identifier.strip_suffix('@').and_then(|name| fields.get(name)) {
+                tokens[idx].typ = typ::Typ::Memory(mem::Token::LoadField(field.clone()));
+            } else if let Some(field) = fields.get(identifier) {
+                if idx + 1 < tokens.len() && tokens[idx + 1].typ == typ::Typ::Memory(mem::Token::Fetch) {
+                    tokens[idx].typ = typ::Typ::Memory(mem::Token::StoreField(field.clone()));
+                    tokens[idx + 1].typ = typ::Typ::Ignore;
+                }
+            }
+        }
+    }
+    for (identifier, (start, _, args, ret, _)) in functions.iter() {
+        for typ in args.iter().chain(ret.iter()).flatten() {
+            if let Some(name) = typ.struct_name() {
+                if !structs.contains(name) {
+                    errors.push(
+                        err::Err::new(
+                            format!("The type `{}` used in the type annotation of `{}` is not a type or a declared struct.", name, identifier),
+                            (tokens[start - 2].row, tokens[start - 2].col, tokens[start - 2].filename.to_string()), tokens[start - 2].len()
+                        )
+                    )
+                }
+            }
+        }
+    }
+
+    for (idx, function) in extern_tokens.iter() {
+        for typ in function.args.iter().chain(function.returns.iter()).flatten() {
+            if let Some(name) = typ.struct_name() {
+                if !structs.contains(name) {
+                    errors.push(
+                        err::Err::new(
+                            format!("The type `{}` used in the type annotation of `{}` is not a type or a declared struct.", name, function.name),
+                            (tokens[*idx].row, tokens[*idx].col, tokens[*idx].filename.to_string()), function.name.len()
+                        )
+                    )
+                }
+            }
+        }
+    }
+
+    for (idx, block) in asm_tokens.iter() {
+        let annotation = &tokens[*idx];
+        for typ in block.args.iter().chain(block.returns.iter()).flatten() {
+            if let Some(name) = typ.struct_name() {
+                if !structs.contains(name) {
+                    errors.push(
+                        err::Err::new(
+                            format!("The type `{}` used in the type annotation of an `asm` block is not a type or a declared struct.", name),
+                            (annotation.row, annotation.col, annotation.filename.to_string()), annotation.value.len() + 2
+                        )
+                    )
+                }
+            }
+        }
+    }
+
+    for (idx, field, typ) in field_tokens.iter() {
+        if let Some(name) = typ.struct_name() {
+            if !structs.contains(name) {
+                errors.push(
+                    err::Err::new(
+                        format!("The type `{}` used by the field `{}` is not a type or a declared struct.", name, field),
+                        (tokens[*idx].row, tokens[*idx].col, tokens[*idx].filename.to_string()), tokens[*idx].len()
+                    )
+                )
+            }
+        }
+    }
+
+    if !args_stack.is_empty() {
         errors.push(
             err::Err::new(
                 "All opening parenthesis '(' must be matched with a closing one ')'.".to_string(),
@@ -288,7 +694,7 @@ pub fn parse<'a>(input:&'a str) -> (
         )
     }
     
-    if errors.len() > 0 {
+    if !errors.is_empty() {
         errors.iter().for_each(|err| {
             err.print();
         });
@@ -296,4 +702,310 @@ pub fn parse<'a>(input:&'a str) -> (
     }
 
     (tokens, functions, consts)
-}
\ No newline at end of file
+}
+
+/// Parse the signature of an extern function or of an exported function,
+/// `tokens` starts at the `extern` or `export` keyword. Those functions are
+/// called with the native calling convention, they return at most one value
+/// and cannot ignore their return (`!`).
+fn parse_native_signature(tokens:&[token::Token]) -> Result<sys::Extern, err::Err> {
+    let keyword = tokens[0].value;
+    let format = match keyword {
+        "export" => "Should be in this format: `export fn <identifier>[<type_annotation>] <statements> end`",
+        _ => "Should be in this format: `extern fn <identifier>[<type_annotation>]`",
+    };
+    let location = |tok:&token::Token| (tok.row, tok.col, tok.filename.to_string());
+    if !matches!(tokens.get(1).map(|tok| &tok.typ), Some(typ::Typ::ControlFlow(control_flow::Token::Fn))) {
+        return Err(err::Err::new(
+            format!("Missing the `fn` keyword just after `{}`. {}", keyword, format),
+            location(&tokens[0]), tokens[0].len()
+        ));
+    }
+    let name = match tokens.get(2).map(|tok| &tok.typ) {
+        Some(typ::Typ::Identifier(name)) => name.to_string(),
+        _ => {
+            return Err(err::Err::new(
+                format!("Missing an `identifier` just after `{} fn`. {}", keyword, format),
+                location(&tokens[1]), tokens[1].len()
+            ));
+        }
+    };
+    match tokens.get(3).map(|tok| &tok.typ) {
+        Some(typ::Typ::Helper(helper::Token::TypeAnnot(args, returns, ignore_return))) => {
+            let is_void = returns.iter().all(|typ| typ == &vec![type_checker::Typ::Void]);
+            if *ignore_return || (returns.len() > 1 && !is_void) {
+                return Err(err::Err::new(
+                    format!("The {} function `{}` must return one value or `void`, without `!`.", keyword, name),
+                    location(&tokens[2]), tokens[2].len()
+                ));
+            }
+            // The length of a `str` is stored before it, the foreign code does
+            // not store it.
+            let has_str = |types:&Vec<Vec<type_checker::Typ>>| types.iter().any(|typ| typ.contains(&type_checker::Typ::Str));
+            let foreign_str = match keyword {
+                "export" if has_str(args) => Some("take a `str` argument"),
+                "extern" if has_str(returns) => Some("return a `str`"),
+                _ => None,
+            };
+            if let Some(foreign_str) = foreign_str {
+                return Err(err::Err::new(
+                    format!("The {} function `{}` cannot {}, the length of a `str` is not known by the foreign code. Use a `ptr` instead.", keyword, name, foreign_str),
+                    location(&tokens[2]), tokens[2].len()
+                ));
+            }
+            Ok(sys::Extern {
+                name,
+                args:args.iter().filter(|typ| *typ != &vec![type_checker::Typ::Void]).cloned().collect(),
+                returns:returns.clone(),
+            })
+        },
+        _ => {
+            Err(err::Err::new(
+                format!("Missing type annotation just after the identifier. {}", format),
+                location(&tokens[2]), tokens[2].len()
+            ))
+        }
+    }
+}
+
+/// Parse an inline assembly block (`asm [<type_annotation>] "<instruction>" ... end`),
+/// with one string for each instruction. `rsp` is the stack of rack, it can
+/// only be changed by pushing and popping values, and `rbp` is kept by the
+/// trampolines of the exported functions.
+fn parse_asm(tokens:&[token::Token]) -> Result<sys::Asm, err::Err> {
+    let format = "Should be in this format: `asm [<type_annotation>] \"<instruction>\" ... end`";
+    let location = |tok:&token::Token| (tok.row, tok.col, tok.filename.to_string());
+    let (args, returns) = match tokens.get(1).map(|tok| &tok.typ) {
+        Some(typ::Typ::Helper(helper::Token::TypeAnnot(args, returns, false))) => (args, returns),
+        _ => {
+            return Err(err::Err::new(
+                format!("Missing type annotation (without `!`) just after the `asm` keyword. {}", format),
+                location(&tokens[0]), tokens[0].len()
+            ));
+        }
+    };
+    let mut instructions = vec![];
+    // The values pushed minus the values popped by the instructions.
+    let mut effect = 0;
+    for tok in tokens[2..tokens.len() - 1].iter() {
+        match tok.typ {
+            typ::Typ::Str(instruction) => {
+                effect += check_asm_instruction(instruction).map_err(|message| {
+                    err::Err::new(message, location(tok), tok.len())
+                })?;
+                instructions.push(instruction.to_string());
+            },
+            _ => {
+                return Err(err::Err::new(
+                    format!("The body of an `asm` block can only contain strings, one for each instruction. {}", format),
+                    location(tok), tok.len()
+                ));
+            }
+        }
+    }
+    if instructions.is_empty() {
+        return Err(err::Err::new(
+            format!("An `asm` block needs at least one instruction. {}", format),
+            location(&tokens[0]), tokens[0].len()
+        ));
+    }
+    let args:Vec<Vec<type_checker::Typ>> = args.iter().filter(|typ| *typ != &vec![type_checker::Typ::Void]).cloned().collect();
+    let declared = returns.iter().filter(|typ| *typ != &vec![type_checker::Typ::Void]).count() as isize - args.len() as isize;
+    if effect != declared {
+        return Err(err::Err::new(
+            format!(
+                "The instructions of the `asm` block change the number of values on the stack by {} but its type annotation declares {}. Each `push` adds a value and each `pop` removes one.",
+                effect, declared
+            ),
+            location(&tokens[0]), tokens[0].len()
+        ));
+    }
+    Ok(sys::Asm {
+        args,
+        returns:returns.clone(),
+        instructions,
+    })
+}
+
+/// Check that an instruction of an `asm` block does not use `rbp` and does not
+/// change `rsp` other than with `push` and `pop`. Return the number of values
+/// it pushes (negative for the values it pops).
+fn check_asm_instruction(instruction:&str) -> Result<isize, String> {
+    let instruction = instruction.trim().to_lowercase();
+    let (mnemonic, operands) = instruction.split_once(char::is_whitespace).unwrap_or((&instruction, ""));
+    if ["call", "ret", "retn", "retf", "enter", "leave"].contains(&mnemonic) {
+        return Err(format!("`{}` changes `rsp` or `rbp` without being a `push` or a `pop`, it cannot be used in an `asm` block.", instruction));
+    }
+    let registers = |operand:&str| operand.split(|c:char| !c.is_ascii_alphanumeric()).map(str::to_string).collect::<Vec<String>>();
+    if registers(operands).iter().any(|register| ["rbp", "ebp", "bp", "bpl"].contains(&register.as_str())) {
+        return Err(format!("`{}` uses `rbp`, it is kept by the exported functions and cannot be used in an `asm` block.", instruction));
+    }
+    let destination = operands.split(',').next().unwrap_or("").trim();
+    let is_exchanged = mnemonic == "xchg" && registers(operands).iter().any(|register| register == "rsp");
+    if is_exchanged || (mnemonic != "push" && ["rsp", "esp", "sp", "spl"].contains(&destination)) {
+        return Err(format!("`{}` changes `rsp`, it is the stack of rack and can only be changed with `push` and `pop` in an `asm` block.", instruction));
+    }
+    Ok(match mnemonic {
+        "push" | "pushf" | "pushfq" => 1,
+        "pop" | "popf" | "popfq" => -1,
+        _ => 0,
+    })
+}
+
+/// Bind the index of a `for` loop in its body. `tokens` starts at the `as` (at
+/// `as_idx`), the identifier of the index is replaced in the body by the index
+/// of the loop, it is read-only. A variable is only written by `put` just after
+/// its identifier (`put(i)` is reordered to `i put` before) and `i!` reads the
+/// index like `i`.
+fn bind_loop_index(tokens:&mut [token::Token], as_idx:usize) -> Result<(), err::Err> {
+    let identifier = match tokens[1].typ {
+        typ::Typ::Identifier(identifier) => identifier,
+        _ => return Ok(()),
+    };
+    tokens[1].typ = typ::Typ::Ignore;
+    for idx in 3..tokens.len() {
+        if tokens[idx].typ == typ::Typ::Identifier(identifier) {
+            match tokens.get(idx + 1).map(|tok| &tok.typ) {
+                Some(typ::Typ::Memory(mem::Token::Put)) => {
+                    return Err(err::Err::new(
+                        format!("`{}` is the index of a `for` loop, it is read-only.", identifier),
+                        (tokens[idx].row, tokens[idx].col, tokens[idx].filename.to_string()), tokens[idx].len()
+                    ));
+                },
+                Some(typ::Typ::Memory(mem::Token::Fetch)) => tokens[idx + 1].typ = typ::Typ::Ignore,
+                _ => {},
+            }
+            tokens[idx].typ = typ::Typ::Memory(mem::Token::LoopIndex(identifier.to_string(), as_idx));
+        }
+    }
+    Ok(())
+}
+
+/// The value matched by an arm of a `case`, an `int` or a `char` literal or const.
+fn arm_value(tok:&token::Token, consts:&HashMap<String, constant::Value>) -> Option<isize> {
+    match &tok.typ {
+        typ::Typ::Int(integer) => Some(*integer),
+        typ::Typ::Char(c) => Some(*c as isize),
+        typ::Typ::Identifier(identifier) => match consts.get(*identifier) {
+            Some(constant::Value::Int(integer)) => Some(*integer),
+            Some(constant::Value::Char(c)) => Some(*c as isize),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Close the `else` before each `elif` of a chain, the whole chain is closed by
+/// a single `end`. `is_chained` is set when the branch that was just closed is
+/// part of a chain.
+fn close_elif_chain(
+    tokens:&mut [token::Token],
+    stack:&mut Vec<(usize, control_flow::Token)>,
+    elif_elses:&[usize],
+    mut is_chained:bool,
+    end:usize,
+) {
+    while is_chained {
+        match stack.pop() {
+            Some((else_idx, control_flow::Token::Else)) => {
+                tokens[else_idx].jmp_idx = Some(end);
+                is_chained = elif_elses.contains(&else_idx);
+            },
+            _ => is_chained = false,
+        }
+    }
+}
+
+/// Parse the declaration of a static buffer (`memory <identifier> <size> end`).
+/// The size is a const expression evaluated at compile time and must be a
+/// positive `int`. Return the name of the buffer and its size in bytes.
+fn parse_memory(tokens:&[token::Token], consts:&HashMap<String, constant::Value>) -> Result<(String, usize), err::Err> {
+    let identifier = match tokens.get(1).map(|tok| &tok.typ) {
+        Some(typ::Typ::Identifier(identifier)) if tokens.len() > 2 => identifier.to_string(),
+        _ => {
+            return Err(err::Err::new(
+                "Missing an `identifier` just after the `memory` keyword. Should be in this format: `memory <identifier> <size> end`".to_string(),
+                (tokens[0].row, tokens[0].col, tokens[0].filename.to_string()), tokens[0].len()
+            ));
+        }
+    };
+    let end = &tokens[tokens.len() - 1];
+    match constant::eval(&tokens[2..tokens.len() - 1], end, consts)? {
+        constant::Value::Int(size) if size > 0 => Ok((identifier, size as usize)),
+        value => {
+            Err(err::Err::new(
+                format!("The size of the memory `{}` must be a positive `int`, found `{}`.", identifier, match value {
+                    constant::Value::Int(size) => size.to_string(),
+                    value => value.typ().to_string(),
+                }),
+                (end.row, end.col, end.filename.to_string()), end.len()
+            ))
+        }
+    }
+}
+
+/// Parse the declaration of a struct (`struct <identifier> <field>:<type> ... end`).
+/// Fields are laid out in the order they are declared, a `char` takes one byte
+/// and every other type takes 8 bytes (a field of a struct type is a pointer).
+/// Each field is aligned to its own size and the size of the struct is rounded
+/// up to a multiple of 8, so `x:int tag:char y:int` puts `y` at the offset 16
+/// and takes 24 bytes. An offset const is created for each field
+/// (`Name.field`) and the size of the struct is available as `Name.size`.
+/// Return the name of the struct, its fields and its size.
+fn parse_struct(tokens:&[token::Token]) -> Result<(String, Vec<mem::Field>, usize), err::Err> {
+    let identifier = match tokens.get(1).map(|tok| &tok.typ) {
+        Some(typ::Typ::Identifier(identifier)) if !identifier.contains(':') => identifier.to_string(),
+        _ => {
+            return Err(err::Err::new(
+                "Missing an `identifier` just after the `struct` keyword. Should be in this format: `struct <identifier> <field>:<type> ... end`".to_string(),
+                (tokens[0].row, tokens[0].col, tokens[0].filename.to_string()), tokens[0].len()
+            ));
+        }
+    };
+    let mut fields:Vec<mem::Field> = vec![];
+    let mut offset:usize = 0;
+    for tok in tokens[2..].iter() {
+        let field = match tok.typ {
+            typ::Typ::Identifier(field) => field.split(':').collect::<Vec<&str>>(),
+            _ => vec![],
+        };
+        let field_typ = match field.as_slice() {
+            [_, typ] => type_checker::Typ::try_from(typ).ok().filter(|typs| typs.len() == 1).map(|typs| typs[0].clone()),
+            _ => None,
+        };
+        let (name, field_typ) = match (field.first(), field_typ) {
+            (Some(name), Some(field_typ)) if field_typ != type_checker::Typ::Void => (name, field_typ),
+            _ => {
+                return Err(err::Err::new(
+                    format!("The fields of the struct `{}` should be in this format: `<field>:<type>` with a single type (not `void`).", identifier),
+                    (tok.row, tok.col, tok.filename.to_string()), tok.len()
+                ));
+            }
+        };
+        let name = format!("{}.{}", identifier, name);
+        if name == format!("{}.size", identifier) || fields.iter().any(|field| field.name == name) {
+            return Err(err::Err::new(
+                format!("The field `{}` is declared more than once or is named `size` which is reserved for the size of the struct.", name),
+                (tok.row, tok.col, tok.filename.to_string()), tok.len()
+            ));
+        }
+        let size = match field_typ {
+            type_checker::Typ::Char => mem::Size::Byte,
+            _ => mem::Size::Qword,
+        };
+        // Each field is aligned to its own size.
+        let field_size = if size == mem::Size::Byte { 1 } else { 8 };
+        offset = offset.next_multiple_of(field_size);
+        fields.push(mem::Field {
+            name,
+            structure:identifier.clone(),
+            offset,
+            size,
+            typ:field_typ,
+        });
+        offset += field_size;
+    }
+    // The size is a multiple of 8 so that the fields of the structs in an
+    // array stay aligned.
+    Ok((identifier, fields, offset.next_multiple_of(8)))
+}
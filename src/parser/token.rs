@@ -1,53 +1,80 @@
 use crate::parser::typ;
 use crate::compiler::{asm, internals, err};
-use crate::type_checker;
 use std::collections::HashMap;
 
 /// A token represent a keyword, string or number. Keywords are separated in
 /// different category (memory, control flow, etc...) only to be easier to find
 /// in the files but are all essentially the same. `jmp_idx` is only used by
-/// control flow tokens for now.
+/// control flow tokens for now. `is_float` is set by the type checker when an
+/// arithmetic or comparison token operates on floats.
 #[derive(Clone, Debug)]
 pub struct Token<'a> {
     pub typ:typ::Typ<'a>,
+    /// The token as it is written in the file (without the quotes of a string
+    /// or a char).
+    pub value:&'a str,
     pub col:usize,
     pub row:usize,
     pub filename:String,
     pub jmp_idx:Option<usize>,
+    pub is_float:bool,
 }
 
 impl<'a> Token<'a> {
-    pub fn new(value:&'a str, is_string:bool, is_char:bool, is_type_annot:bool, row:usize, col:usize, filename:&str) -> Result<Self, ()> {
+    /// Create a new token from its value. Fails with `None` if there is no token
+    /// to create (the value is empty) or with the error to report.
+    pub fn new(value:&'a str, is_string:bool, is_char:bool, is_type_annot:bool, row:usize, col:usize, filename:&str) -> Result<Self, Option<err::Err>> {
         match typ::Typ::try_from(value, is_string, is_char, is_type_annot) {
             Ok(typ) => {
                 Ok(
                     Self {
                         typ,
+                        value,
                         row,
                         col,
                         filename:filename.to_string(),
                         jmp_idx:None,
+                        is_float:false,
                     }
                 )
             },
-            Err(()) => {
-                Err(())
-            } 
+            Err(message) => {
+                Err(message.map(|message| {
+                    err::Err::new(message, (row, col, filename.to_string()), value.len())
+                }))
+            }
         }
 
     }
 
+    /// The string literal written with the `s` prefix, a slice.
+    pub fn into_slice(self) -> Self {
+        match self.typ {
+            typ::Typ::Str(string) => Self { typ:typ::Typ::Slice(string), ..self },
+            _ => self,
+        }
+    }
+
     /// Will return the length of the token as the token appears in a file. This
     /// is useful for printing helpful error message.
     pub fn len(&self) -> usize {
         match self.typ.clone() {
             typ::Typ::Arithmetic(_) => 1,
             typ::Typ::Str(string) => string.len(),
+            typ::Typ::Slice(string) => string.len() + 1,
             typ::Typ::Identifier(identifier) => identifier.len(),
             typ::Typ::Int(integer) => integer.to_string().len(),
+            typ::Typ::Char(_) => 3,
+            typ::Typ::Float(float) => float.to_string().len(),
+            typ::Typ::Cast(cast) => cast.to_string().len(),
             typ::Typ::Helper(_) => 1,
             typ::Typ::Sys(typ::sys::Token::Sys(identifier)) => identifier.len() + 5,
+            typ::Typ::Sys(typ::sys::Token::Call(function)) => function.name.len() + 5,
             typ::Typ::Sys(typ::sys::Token::Include) => 7,
+            typ::Typ::Sys(typ::sys::Token::Extern) | typ::Typ::Sys(typ::sys::Token::Export)
+            | typ::Typ::Sys(typ::sys::Token::Exported(_)) => 6,
+            typ::Typ::Sys(typ::sys::Token::ExportAddress(name)) => name.len() + 7,
+            typ::Typ::Sys(typ::sys::Token::Asm(_)) => 3,
             typ::Typ::Ignore => 0,
             typ::Typ::Memory(token) => {
                 match token {
@@ -55,18 +82,14 @@ impl<'a> Token<'a> {
                     typ::mem::Token::InternalMem | typ::mem::Token::Swap 
                     | typ::mem::Token::Load(_) | typ::mem::Token::Drop | typ::mem::Token::Over => 4,
                     typ::mem::Token::Store(_) | typ::mem::Token::Fetch => 5,
+                    typ::mem::Token::LoadField(field) | typ::mem::Token::StoreField(field) => field.name.len() + 1,
+                    typ::mem::Token::Reserve(_, _) => 6,
+                    typ::mem::Token::Static(name) => name.len(),
+                    typ::mem::Token::LoopIndex(name, _) => name.len(),
+                    typ::mem::Token::FnAddress(name) => name.len() + 1,
                 }
             }
-            typ::Typ::ControlFlow(token) => {
-                match token {
-                    typ::control_flow::Token::Do | typ::control_flow::Token::Fn | typ::control_flow::Token::If => 2,
-                    typ::control_flow::Token::End | typ::control_flow::Token::EndWhile => 3,
-                    typ::control_flow::Token::Else => 4,
-                    typ::control_flow::Token::Const | typ::control_flow::Token::Macro 
-                    | typ::control_flow::Token::While => 5,
-
-                }
-            }
+            typ::Typ::ControlFlow(token) => token.to_string().len(),
             typ::Typ::Comparison(token) => {
                 match token {
                     typ::cmp::Token::Eq | typ::cmp::Token::Gt | typ::cmp::Token::Lt => 1,
@@ -83,8 +106,8 @@ impl<'a> Token<'a> {
     pub fn compile(
         &self, 
         internals:&mut internals::Internals, 
-        functions:&HashMap<String, (usize, usize, Vec<Vec<type_checker::Typ>>, Vec<Vec<type_checker::Typ>>, bool)>,
-        consts:&HashMap<String, isize>,
+        functions:&crate::parser::Functions,
+        consts:&HashMap<String, super::constant::Value>,
     ) -> Result<Vec<asm::Inst>, err::Err> {
         let mut was_identifier = false;
         if internals.current_variable.is_some() && !self.typ.is_affected_by_identifier() {
@@ -101,10 +124,11 @@ impl<'a> Token<'a> {
         let toks = match &self.typ {
             typ::Typ::Memory(typ) => typ.compile(internals),
             typ::Typ::ControlFlow(typ) => typ.compile(self.jmp_idx, internals),
-            typ::Typ::Comparison(typ) => typ.compile(),
-            typ::Typ::Arithmetic(typ) => typ.compile(),
-            typ::Typ::Sys(typ) => typ.compile(consts, internals),
-            typ::Typ::Str(string) => {
+            typ::Typ::Comparison(typ) => typ.compile(self.is_float),
+            typ::Typ::Arithmetic(typ) => typ.compile(self.is_float),
+            typ::Typ::Cast(typ) => typ.compile(),
+            typ::Typ::Sys(typ) => typ.compile(internals),
+            typ::Typ::Str(string) | typ::Typ::Slice(string) => {
                 let str_idx = internals.push_string(string.to_string());
                 Ok(vec![
                     asm::Inst::Lea(asm::Op::Rax, asm::Op::Memory(format!("[str_{}]", str_idx))),
@@ -117,17 +141,27 @@ impl<'a> Token<'a> {
                     asm::Inst::Push(asm::Op::Rax)
                 ])
             }
+            typ::Typ::Char(c) => {
+                Ok(vec![
+                    asm::Inst::Mov(asm::Op::Rax, asm::Op::Immediate(*c as isize)),
+                    asm::Inst::Push(asm::Op::Rax)
+                ])
+            }
+            typ::Typ::Float(float) => {
+                Ok(vec![
+                    asm::Inst::Mov(asm::Op::Rax, asm::Op::Immediate(float.to_bits() as isize)),
+                    asm::Inst::Push(asm::Op::Rax)
+                ])
+            }
             typ::Typ::Identifier(identifier) => {
                 was_identifier = true;
                 let id = identifier.to_string();
-                if let Some(_) = functions.get(&id) {
+                if functions.get(&id).is_some() {
                     Ok(vec![
                         asm::Inst::Call(asm::Op::Label(id))
                     ])
                 } else if let Some(value) = consts.get(&id) {
-                    Ok(vec![
-                        asm::Inst::Push(asm::Op::Immediate(*value))
-                    ])
+                    Ok(value.compile(internals))
                 } else {
                     internals.current_variable = Some(identifier.to_string());
                     Ok(vec![])
@@ -143,10 +177,39 @@ impl<'a> Token<'a> {
     }
 }
 
+/// Push the token that was just created, or the error if the token could not be
+/// created. Empty values do not produce any token and are ignored.
+fn push_token<'a>(tokens:&mut Vec<Token<'a>>, errors:&mut Vec<err::Err>, token:Result<Token<'a>, Option<err::Err>>) {
+    match token {
+        Ok(tok) => tokens.push(tok),
+        Err(Some(error)) => errors.push(error),
+        Err(None) => {},
+    }
+}
+
+/// If the `<` or `>` at `col` is part of the word that started at `start` instead
+/// of being a comparison.
+fn is_part_of_word(line:&str, start:usize, col:usize) -> bool {
+    let word = line[start..col].trim_start();
+    if line[col..].starts_with('<') {
+        word == "ptr" || word.ends_with(":ptr") || word.ends_with("<ptr")
+    } else {
+        word.matches('<').count() > word.matches('>').count()
+            || (word.ends_with(char::is_alphabetic) && line[col + 1..].starts_with(char::is_alphabetic))
+    }
+}
+
 /// Convert the text input of the file to a string of tokens. The string of
 /// token will then be consume one by one to compile the program.
-pub fn tokenize(input:&str) -> Result<Vec<Token>, Vec<err::Err>> {
+pub fn tokenize(input:&str) -> Result<Vec<Token<'_>>, Vec<err::Err>> {
+    tokenize_with_comments(input).map(|(tokens, _)| tokens)
+}
+
+/// Like `tokenize`, but the comments (from the `#` to the end of the line) are
+/// also returned, for the tools reading the source without compiling it.
+pub fn tokenize_with_comments(input:&str) -> Result<(Vec<Token<'_>>, Vec<&str>), Vec<err::Err>> {
     let mut tokens = vec![];
+    let mut comments = vec![];
     let mut errors = vec![];
     let mut filename = "";
     let mut row_offset = 0;
@@ -154,8 +217,11 @@ pub fn tokenize(input:&str) -> Result<Vec<Token>, Vec<err::Err>> {
         let mut start = 0;
         let mut in_comment = false;
         let mut is_string = false;
+        let mut is_slice = false;
         let mut is_char = false;
         let mut is_type_annot = false;
+        // The brackets opened inside a type annotation (by `fn[...]` types).
+        let mut annot_depth = 0;
         let mut is_two_char_tok = false;
         let mut ignore_equal = false;
         // This delimit a file, (when included, file are all put in the same
@@ -180,28 +246,47 @@ pub fn tokenize(input:&str) -> Result<Vec<Token>, Vec<err::Err>> {
                         start = col;
                     }
                     let value = &line[two_tok_start..end].trim();
-                    if let Ok(tok) = Token::new(value, false, false, false, row - row_offset, col, filename) {
-                        tokens.push(tok);
-                    }
+                    push_token(&mut tokens, &mut errors, Token::new(value, false, false, false, row - row_offset, col, filename));
                     is_two_char_tok = false;
                 }
                 match c {
                     '#' => {
+                        comments.push(&line[col..]);
                         in_comment = true;
                     }
+                    // The arrow of the arms of a `case` (`=>`).
+                    '=' if !is_string && !is_char && !is_type_annot && line[col + 1..].starts_with('>') => {
+                        let value = &line[start..col].trim();
+                        push_token(&mut tokens, &mut errors, Token::new(value, false, false, false, row - row_offset, col, filename));
+                        start = col;
+                    },
+                    '>' if !is_string && !is_char && !is_type_annot && line[..col].ends_with('=') && line[start..col].trim() == "=" => {
+                        push_token(&mut tokens, &mut errors, Token::new(&line[start..=col], false, false, false, row - row_offset, col, filename));
+                        start = col + 1;
+                    },
+                    // A `>` between two letters is part of a conversion keyword
+                    // (like `int>float`) and `<` and `>` are part of a typed
+                    // pointer (like `ptr<int>` in the field of a struct).
+                    '<' | '>' if !is_string && !is_char && !is_type_annot && is_part_of_word(line, start, col) => {
+                        if col == line.len() - 1 {
+                            push_token(&mut tokens, &mut errors, Token::new(&line[start..], false, false, false, row - row_offset, col, filename));
+                        }
+                    },
                     '!' | '>' | '<' => if !is_string && !is_char && !is_type_annot {
                         is_two_char_tok = true;
                         let value = &line[start..col].trim();
-                        if let Ok(tok) = Token::new(value, false, false, false, row - row_offset, col, filename) {
-                            tokens.push(tok);
-                        }
+                        push_token(&mut tokens, &mut errors, Token::new(value, false, false, false, row - row_offset, col, filename));
                         if col == line.len() - 1 {
-                            if let Ok(tok) = Token::new(&line[col..], false, false, false, row - row_offset, col, filename) {
-                                tokens.push(tok);
-                            }
+                            push_token(&mut tokens, &mut errors, Token::new(&line[col..], false, false, false, row - row_offset, col, filename));
                         }
                         start = col;
                     },
+                    // `&<identifier>` is the address of a function, not a
+                    // logical and.
+                    '&' if !is_string && !is_char && !is_type_annot && line[start..col].trim().is_empty()
+                        && line[col + 1..].starts_with(|c:char| c.is_alphabetic() || c == '_') => {
+                        start = col;
+                    },
                     '(' | ')' | '&' | '|' | '+' | '*'
                     | '/' | '%' | '=' | ',' | ' ' | '\t' => {
                         if !is_char && !is_string && !is_type_annot {
@@ -210,21 +295,17 @@ pub fn tokenize(input:&str) -> Result<Vec<Token>, Vec<err::Err>> {
                                 start = col + 1;
                             } else {
                                 let value = &line[start..col].trim();
-                                if let Ok(tok) = Token::new(value, false, false, false, row - row_offset, col, filename) {
-                                    tokens.push(tok);
-                                }
-                                if let Ok(tok) = Token::new(&line[col..col + 1], false, false, false, row - row_offset, col, filename) {
-                                    tokens.push(tok);
-                                }
+                                push_token(&mut tokens, &mut errors, Token::new(value, false, false, false, row - row_offset, col, filename));
+                                push_token(&mut tokens, &mut errors, Token::new(&line[col..col + 1], false, false, false, row - row_offset, col, filename));
                                 start = col + 1;
                             }
                         }
                     },
+                    '[' if is_type_annot => annot_depth += 1,
+                    ']' if is_type_annot && annot_depth > 0 => annot_depth -= 1,
                     '[' => {
                         let value = &line[start..col].trim();
-                        if let Ok(tok) = Token::new(value, false, false, false, row - row_offset, col, filename) {
-                            tokens.push(tok);
-                        }
+                        push_token(&mut tokens, &mut errors, Token::new(value, false, false, false, row - row_offset, col, filename));
                         if !is_string && !is_char {
                             start = col + 1;
                             is_type_annot = true;
@@ -233,9 +314,7 @@ pub fn tokenize(input:&str) -> Result<Vec<Token>, Vec<err::Err>> {
                     ']' => {
                         if is_type_annot {
                             let value = &line[start..col].trim();
-                            if let Ok(tok) = Token::new(value, false, false, true, row - row_offset, col, filename) {
-                                tokens.push(tok);
-                            }
+                            push_token(&mut tokens, &mut errors, Token::new(value, false, false, true, row - row_offset, col, filename));
                             start = col + 1;
                             is_type_annot = false;
                         }
@@ -243,15 +322,17 @@ pub fn tokenize(input:&str) -> Result<Vec<Token>, Vec<err::Err>> {
                     '"' => {
                         if is_string {
                             let value = &line[start..col].trim();
-                            if let Ok(tok) = Token::new(value, true, false, false, row - row_offset, col, filename) {
-                                tokens.push(tok);
-                            }
+                            let token = Token::new(value, true, false, false, row - row_offset, col, filename);
+                            push_token(&mut tokens, &mut errors, if is_slice { token.map(Token::into_slice) } else { token });
                             start = col + 1;
                             is_string = false;
                         } else if !is_char && !is_type_annot {
+                            // A `s` just before the quote makes the string a
+                            // slice (`s"hello"`).
                             let value = &line[start..col].trim();
-                            if let Ok(tok) = Token::new(value, false, false, false, row - row_offset, col, filename) {
-                                tokens.push(tok);
+                            is_slice = *value == "s" && line[..col].ends_with('s');
+                            if !is_slice {
+                                push_token(&mut tokens, &mut errors, Token::new(value, false, false, false, row - row_offset, col, filename));
                             }
                             start = col + 1;
                             is_string = true;
@@ -261,9 +342,7 @@ pub fn tokenize(input:&str) -> Result<Vec<Token>, Vec<err::Err>> {
                         if is_char {
                             if col - start == 1 {
                                 let value = &line[col - 1..col];
-                                if let Ok(tok) = Token::new(value, false, true, false, row - row_offset, col, filename) {
-                                    tokens.push(tok);
-                                }
+                                push_token(&mut tokens, &mut errors, Token::new(value, false, true, false, row - row_offset, col, filename));
                                 start = col + 1;
                                 is_char = false;
                             } else {
@@ -278,9 +357,7 @@ pub fn tokenize(input:&str) -> Result<Vec<Token>, Vec<err::Err>> {
                             }
                         } else if !is_string && !is_type_annot {
                             let value = &line[start..col];
-                            if let Ok(tok) = Token::new(value, false, false, false, row - row_offset, col, filename) {
-                                tokens.push(tok);
-                            }
+                            push_token(&mut tokens, &mut errors, Token::new(value, false, false, false, row - row_offset, col, filename));
                             start = col + 1;
                             is_char = true;
                         }
@@ -289,9 +366,7 @@ pub fn tokenize(input:&str) -> Result<Vec<Token>, Vec<err::Err>> {
                         if col == line.len() - 1 {
                             if !is_string && !is_char && !is_type_annot {
                                 let value = &line[start..];
-                                if let Ok(tok) = Token::new(value, false, false, false, row - row_offset, col, filename) {
-                                    tokens.push(tok);
-                                }
+                                push_token(&mut tokens, &mut errors, Token::new(value, false, false, false, row - row_offset, col, filename));
                             } else if !is_type_annot {
                                 errors.push(
                                     err::Err::new(
@@ -316,9 +391,9 @@ pub fn tokenize(input:&str) -> Result<Vec<Token>, Vec<err::Err>> {
     // tokens.iter().for_each(|tok| {
         // println!("{:?}", tok);
     // });
-    if errors.len() > 0 {
+    if !errors.is_empty() {
         Err(errors)
     } else {
-        Ok(tokens)
+        Ok((tokens, comments))
     }
 }
\ No newline at end of file
@@ -8,40 +8,43 @@ pub enum Typ<'a> {
     Arithmetic(arith::Token),
     Sys(sys::Token),
     Helper(helper::Token),
+    Cast(cast::Token),
     Str(&'a str),
+    /// A string literal prefixed with `s` (`s"hello"`), its length is known.
+    Slice(&'a str),
     Int(isize),
+    Char(char),
+    Float(f64),
     Identifier(&'a str),
     Ignore,
 }
 
 impl<'a> Typ<'a> {
-    /// Will try to find what type of token the given string is. Currently it
-    /// never fails but this may change soon when some checks are added
-    /// (especially for char autorized in identifiers).
-    pub fn try_from(value:&'a str, is_string:bool, is_char:bool, is_type_annot:bool) -> Result<Self, ()> {
-        if value.trim().len() == 0 {
-            Err(())
+    /// Will try to find what type of token the given string is. Fails with
+    /// `None` when there is no token to create (an empty value) and with an
+    /// error message when the value is not a valid token (like an integer
+    /// literal out of range).
+    pub fn try_from(value:&'a str, is_string:bool, is_char:bool, is_type_annot:bool) -> Result<Self, Option<String>> {
+        if value.trim().is_empty() {
+            Err(None)
         } else if is_char {
-            let int = value.chars().nth(0).unwrap() as isize;
-            Ok(Typ::Int(int))
+            Ok(Typ::Char(value.chars().next().unwrap()))
         } else if is_string {
-            if value.len() > 0 {
-                Ok(Typ::Str(&value))
+            if let Some(c) = value.chars().find(|c| c.is_control() && *c != '\t') {
+                Err(Some(format!("A string cannot contain the control character {:?}, it cannot be written in the assembly.", c)))
+            } else if !value.is_empty() {
+                Ok(Typ::Str(value))
             } else {
-                Err(())
+                Err(None)
             }
         } else if is_type_annot {
             match helper::Token::new_type_annot(value) {
                 Ok(annot) => Ok(Typ::Helper(annot)),
-                // TODO: This error is never shown when it happen.
-                Err(_str) => {
-                    println!("{}", _str);
-                    unimplemented!("type annotation error");
-                },
+                Err(err) => Err(Some(err)),
             }
         } else {
-            if let Ok(integer) = value.parse::<isize>() {
-                Ok(Typ::Int(integer))
+            if let Some(number) = Typ::parse_number(value) {
+                number.map_err(Some)
             } else {
                 let typ = match value {
                     "mem" =>   Typ::Memory(mem::Token::Mem),
@@ -78,16 +81,39 @@ impl<'a> Typ<'a> {
                     "macro" => Typ::ControlFlow(control_flow::Token::Macro),
                     "fn" => Typ::ControlFlow(control_flow::Token::Fn),
                     "const" => Typ::ControlFlow(control_flow::Token::Const),
+                    "struct" => Typ::ControlFlow(control_flow::Token::Struct),
+                    "memory" => Typ::ControlFlow(control_flow::Token::Memory),
+                    "asm" => Typ::ControlFlow(control_flow::Token::Asm),
                     "if" => Typ::ControlFlow(control_flow::Token::If),
                     "else" => Typ::ControlFlow(control_flow::Token::Else),
+                    "elif" => Typ::ControlFlow(control_flow::Token::Elif),
+                    "for" => Typ::ControlFlow(control_flow::Token::For),
+                    "as" => Typ::ControlFlow(control_flow::Token::As),
+                    "case" => Typ::ControlFlow(control_flow::Token::Case),
+                    "of" => Typ::ControlFlow(control_flow::Token::Of(vec![])),
+                    "=>" => Typ::ControlFlow(control_flow::Token::Arm),
                     "while" => Typ::ControlFlow(control_flow::Token::While),
                     "do" => Typ::ControlFlow(control_flow::Token::Do),
                     "end" => Typ::ControlFlow(control_flow::Token::End),
+                    "ret" => Typ::ControlFlow(control_flow::Token::Ret),
+                    "break" => Typ::ControlFlow(control_flow::Token::Break),
+                    "continue" => Typ::ControlFlow(control_flow::Token::Continue),
+                    "call" => Typ::ControlFlow(control_flow::Token::Call),
+                    "int>float" => Typ::Cast(cast::Token::IntToFloat),
+                    "float>int" => Typ::Cast(cast::Token::FloatToInt),
+                    "int>char" => Typ::Cast(cast::Token::IntToChar),
+                    "char>int" => Typ::Cast(cast::Token::CharToInt),
+                    "slice>str" => Typ::Cast(cast::Token::SliceToStr),
+                    "str>slice" => Typ::Cast(cast::Token::StrToSlice),
+                    "ptr>slice_unchecked" => Typ::Cast(cast::Token::PtrToSliceUnchecked),
                     "sys" => Typ::Sys(sys::Token::Sys("".to_string())),
                     "include" => Typ::Sys(sys::Token::Include),
+                    "extern" => Typ::Sys(sys::Token::Extern),
+                    "export" => Typ::Sys(sys::Token::Export),
                     "(" => Typ::Helper(helper::Token::ArgOpen),
                     ")" => Typ::Helper(helper::Token::ArgClose),
                     "," => Typ::Helper(helper::Token::ArgSep),
+                    _ if value.len() > 1 && value.starts_with('&') => Typ::Memory(mem::Token::FnAddress(value[1..].to_string())),
                     _ => {
                         let path:Vec<&str> = value.split("::").collect();
                         if path.len() > 1 {
@@ -105,6 +131,53 @@ impl<'a> Typ<'a> {
         }
     }
 
+    /// Parse a number literal. Integers can be written in decimal, hexadecimal
+    /// (`0x`), binary (`0b`) and octal (`0o`) and digits can be separated by `_`
+    /// (`1_000_000`). Prefixed literals can use all 64 bits (`0xFFFFFFFFFFFFFFFF`
+    /// is `-1`) but decimal literals must fit in an `int`. Decimal literals with
+    /// a `.` or an exponent (`1.5`, `2e10`) are floats. Return `None` if the
+    /// value does not start with a digit (it is not a number at all).
+    fn parse_number(value:&str) -> Option<Result<Self, String>> {
+        let (is_negative, literal) = match value.strip_prefix('-') {
+            Some(literal) => (true, literal),
+            None => (false, value),
+        };
+        if !literal.starts_with(|c:char| c.is_ascii_digit()) {
+            return None;
+        }
+        let (radix, digits) = match literal.get(..2) {
+            Some("0x") | Some("0X") => (16, &literal[2..]),
+            Some("0b") | Some("0B") => (2, &literal[2..]),
+            Some("0o") | Some("0O") => (8, &literal[2..]),
+            _ => (10, literal),
+        };
+        let digits = digits.replace('_', "");
+        if radix == 10 && digits.contains(['.', 'e', 'E']) {
+            return Some(match value.replace('_', "").parse::<f64>() {
+                Ok(float) if float.is_finite() => Ok(Typ::Float(float)),
+                Ok(_) => Err(format!("The float literal `{}` does not fit in a `float` (64 bits).", value)),
+                Err(_) => Err(format!("The float literal `{}` is not a valid float.", value)),
+            });
+        }
+        if digits.is_empty() {
+            return Some(Err(format!("The integer literal `{}` has no digits after its prefix.", value)));
+        }
+        if let Some(digit) = digits.chars().find(|c| !c.is_digit(radix)) {
+            return Some(Err(format!("The integer literal `{}` contains `{}` which is not a valid digit in base {}.", value, digit, radix)));
+        }
+        let integer = if radix == 10 {
+            format!("{}{}", if is_negative { "-" } else { "" }, digits).parse::<isize>().ok()
+        } else {
+            u64::from_str_radix(&digits, radix).ok().map(|integer| {
+                let integer = integer as isize;
+                if is_negative { integer.wrapping_neg() } else { integer }
+            })
+        };
+        Some(integer.map(Typ::Int).ok_or(format!(
+            "The integer literal `{}` does not fit in an `int` (64 bits).", value
+        )))
+    }
+
     /// Return if the current token is affected by an identifier as the previous
     /// token.
     pub fn is_affected_by_identifier(&self) -> bool {
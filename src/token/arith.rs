@@ -12,11 +12,43 @@ pub enum Token {
 }
 
 impl Token {
-    pub fn compile(&self) -> Result<Vec<Inst>, err::Err> {
+    /// Evaluate the operation at compile time, with the same semantics as the
+    /// compiled operation (`*` wraps, `/` and `%` are unsigned).
+    pub fn eval(&self, lhs:isize, rhs:isize) -> Result<isize, String> {
+        if matches!(self, Token::Div | Token::Mod) && rhs == 0 {
+            return Err("Division by zero.".to_string());
+        }
+        Ok(match self {
+            Token::Plus => lhs.wrapping_add(rhs),
+            Token::Minus => lhs.wrapping_sub(rhs),
+            Token::Mul => lhs.wrapping_mul(rhs),
+            Token::Div => ((lhs as u64) / (rhs as u64)) as isize,
+            Token::Mod => ((lhs as u64) % (rhs as u64)) as isize,
+            Token::LogicalAnd => lhs & rhs,
+            Token::LogicalOr => lhs | rhs,
+        })
+    }
+
+    /// Evaluate the operation on floats at compile time. Only `+`, `-`, `*` and
+    /// `/` are available for floats.
+    pub fn eval_float(&self, lhs:f64, rhs:f64) -> Result<f64, String> {
+        match self {
+            Token::Plus => Ok(lhs + rhs),
+            Token::Minus => Ok(lhs - rhs),
+            Token::Mul => Ok(lhs * rhs),
+            Token::Div => Ok(lhs / rhs),
+            _ => Err("Only `+`, `-`, `*` and `/` can be used with values of type `float`.".to_string()),
+        }
+    }
+
+    pub fn compile(&self, is_float:bool) -> Result<Vec<Inst>, err::Err> {
         let mut output = vec![
             Inst::Pop(Op::Rbx),
             Inst::Pop(Op::Rax),
         ];  
+        if is_float {
+            return Ok(self.compile_float(output));
+        }
         match self {
             Token::Plus => output.push(Inst::Add(Op::Rax, Op::Rbx)),
             Token::Minus => output.push(Inst::Sub(Op::Rax, Op::Rbx)),
@@ -40,4 +72,23 @@ impl Token {
         output.push(Inst::Push(Op::Rax));
         Ok(output)
     }
+
+    /// Floats are stored on the stack as their raw bits. They are moved to the
+    /// sse registers for the operation and the result is pushed back as bits.
+    fn compile_float(&self, mut output:Vec<Inst>) -> Vec<Inst> {
+        let inst = match self {
+            Token::Plus => "addsd",
+            Token::Minus => "subsd",
+            Token::Mul => "mulsd",
+            _ => "divsd",
+        };
+        output.append(&mut vec![
+            Inst::Inst2Op("movq", Op::Xmm0, Op::Rax),
+            Inst::Inst2Op("movq", Op::Xmm1, Op::Rbx),
+            Inst::Inst2Op(inst, Op::Xmm0, Op::Xmm1),
+            Inst::Inst2Op("movq", Op::Rax, Op::Xmm0),
+            Inst::Push(Op::Rax),
+        ]);
+        output
+    }
 }
\ No newline at end of file
@@ -0,0 +1,75 @@
+use crate::compiler::{asm::*, err};
+use crate::type_checker;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Token {
+    IntToFloat,
+    FloatToInt,
+    IntToChar,
+    CharToInt,
+    /// A slice is also a c-string, its chars are followed by a 0.
+    SliceToStr,
+    /// A `str` is a literal or a converted slice, its length is already
+    /// stored before it.
+    StrToSlice,
+    /// The pointer must point after the length of the string, like the
+    /// pointers returned by `slice::from`. Nothing is checked, a pointer to
+    /// anything else gives a slice with a garbage length, hence the name.
+    PtrToSliceUnchecked,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        let cast = match self {
+            Token::IntToFloat => "int>float",
+            Token::FloatToInt => "float>int",
+            Token::IntToChar => "int>char",
+            Token::CharToInt => "char>int",
+            Token::SliceToStr => "slice>str",
+            Token::StrToSlice => "str>slice",
+            Token::PtrToSliceUnchecked => "ptr>slice_unchecked",
+        };
+        write!(f, "{}", cast)
+    }
+}
+
+impl Token {
+    /// The type expected on the stack and the type pushed back by the cast.
+    pub fn types(&self) -> (type_checker::Typ, type_checker::Typ) {
+        match self {
+            Token::IntToFloat => (type_checker::Typ::Int, type_checker::Typ::Float),
+            Token::FloatToInt => (type_checker::Typ::Float, type_checker::Typ::Int),
+            Token::IntToChar => (type_checker::Typ::Int, type_checker::Typ::Char),
+            Token::CharToInt => (type_checker::Typ::Char, type_checker::Typ::Int),
+            Token::SliceToStr => (type_checker::Typ::Slice, type_checker::Typ::Str),
+            Token::StrToSlice => (type_checker::Typ::Str, type_checker::Typ::Slice),
+            Token::PtrToSliceUnchecked => (type_checker::Typ::Ptr, type_checker::Typ::Slice),
+        }
+    }
+
+    pub fn compile(&self) -> Result<Vec<Inst>, err::Err> {
+        let mut output = vec![
+            Inst::Pop(Op::Rax),
+        ];
+        match self {
+            Token::IntToFloat => {
+                output.append(&mut vec![
+                    Inst::Inst2Op("cvtsi2sd", Op::Xmm0, Op::Rax),
+                    Inst::Inst2Op("movq", Op::Rax, Op::Xmm0),
+                ]);
+            },
+            Token::FloatToInt => {
+                output.append(&mut vec![
+                    Inst::Inst2Op("movq", Op::Xmm0, Op::Rax),
+                    Inst::Inst2Op("cvttsd2si", Op::Rax, Op::Xmm0),
+                ]);
+            },
+            // Only the lowest byte of an int is kept as a char.
+            Token::IntToChar => output.push(Inst::Inst2Op("and", Op::Rax, Op::Immediate(0xFF))),
+            Token::CharToInt | Token::SliceToStr | Token::StrToSlice | Token::PtrToSliceUnchecked => {},
+        };
+        output.push(Inst::Push(Op::Rax));
+        Ok(output)
+    }
+}
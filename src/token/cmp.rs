@@ -11,21 +11,64 @@ pub enum Token {
 }
 
 impl Token {
-    pub fn compile(&self) -> Result<Vec<Inst>, err::Err> {
+    /// Evaluate the comparison at compile time. Push 1 if true or 0 if false
+    /// like the compiled comparison.
+    pub fn eval<T:PartialOrd>(&self, lhs:T, rhs:T) -> isize {
+        let result = match self {
+            Token::Eq => lhs == rhs,
+            Token::NotEq => lhs != rhs,
+            Token::Le => lhs <= rhs,
+            Token::Lt => lhs < rhs,
+            Token::Ge => lhs >= rhs,
+            Token::Gt => lhs > rhs,
+        };
+        result as isize
+    }
+
+    pub fn compile(&self, is_float:bool) -> Result<Vec<Inst>, err::Err> {
         let mut output = vec![
             Inst::Xor(Op::Rcx, Op::Rcx),
             Inst::Mov(Op::Rdx, Op::Immediate(1)),
             Inst::Pop(Op::Rbx),
             Inst::Pop(Op::Rax),
-            Inst::Cmp(Op::Rax, Op::Rbx),
         ];  
-        let inst = match self {
-            Token::Eq => "cmove",
-            Token::NotEq => "cmovne",
-            Token::Le => "cmovle",
-            Token::Lt => "cmovl",
-            Token::Ge => "cmovge",
-            Token::Gt => "cmovg",
+        // Floats are compared with `ucomisd` which sets the flags like an
+        // unsigned comparison would. A NaN sets the parity flag and the flags of
+        // an equality (and of a below), so `<` and `<=` compare the swapped
+        // operands with `cmova`/`cmovae` and `=`/`!=` check the parity flag:
+        // every comparison with a NaN is false except `!=`.
+        let inst = if is_float {
+            let (lhs, rhs) = match self {
+                Token::Le | Token::Lt => (Op::Xmm1, Op::Xmm0),
+                _ => (Op::Xmm0, Op::Xmm1),
+            };
+            output.append(&mut vec![
+                Inst::Inst2Op("movq", Op::Xmm0, Op::Rax),
+                Inst::Inst2Op("movq", Op::Xmm1, Op::Rbx),
+                Inst::Inst2Op("ucomisd", lhs, rhs),
+            ]);
+            match self {
+                Token::Eq => {
+                    output.push(Inst::Inst2Op("cmovp", Op::Rdx, Op::Rcx));
+                    "cmove"
+                },
+                Token::NotEq => {
+                    output.push(Inst::Inst2Op("cmovp", Op::Rcx, Op::Rdx));
+                    "cmovne"
+                },
+                Token::Le | Token::Ge => "cmovae",
+                Token::Lt | Token::Gt => "cmova",
+            }
+        } else {
+            output.push(Inst::Cmp(Op::Rax, Op::Rbx));
+            match self {
+                Token::Eq => "cmove",
+                Token::NotEq => "cmovne",
+                Token::Le => "cmovle",
+                Token::Lt => "cmovl",
+                Token::Ge => "cmovge",
+                Token::Gt => "cmovg",
+            }
         };
         output.push(Inst::Inst2Op(inst, Op::Rcx, Op::Rdx));
         output.push(Inst::Push(Op::Rcx));
@@ -1,4 +1,8 @@
 use crate::compiler::{asm::*, internals, err};
+use std::fmt;
+
+/// The values of the arms of a `case` and the index of their `=>`.
+pub type Arms = Vec<(isize, usize)>;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
@@ -11,39 +15,79 @@ pub enum Token {
     Macro,
     Fn,
     Const,
+    Struct,
+    Memory,
+    Asm,
+    Ret,
+    Break,
+    Continue,
+    Elif,
+    Case,
+    Of(Arms),
+    Arm,
+    For,
+    As,
+    Call,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        let keyword = match self {
+            Token::EndWhile | Token::End => "end",
+            Token::If => "if",
+            Token::While => "while",
+            Token::Do => "do",
+            Token::Else => "else",
+            Token::Macro => "macro",
+            Token::Fn => "fn",
+            Token::Const => "const",
+            Token::Struct => "struct",
+            Token::Memory => "memory",
+            Token::Asm => "asm",
+            Token::Ret => "ret",
+            Token::Break => "break",
+            Token::Continue => "continue",
+            Token::Elif => "elif",
+            Token::Case => "case",
+            Token::Of(_) => "of",
+            Token::Arm => "=>",
+            Token::For => "for",
+            Token::As => "as",
+            Token::Call => "call",
+        };
+        write!(f, "{}", keyword)
+    }
 }
 
 impl Token {
     pub fn compile(&self, jmp_idx:Option<usize>, internals:&mut internals::Internals) -> Result<Vec<Inst>, err::Err> {
         // unimplemented!("Control flow is not implemented yet");
         match self {
-            Token::If | Token::Do => {
+            Token::If | Token::Elif | Token::Do => {
                 let mut output = vec![
                     Inst::Pop(Op::Rax),
                     Inst::Inst2Op("test", Op::Rax, Op::Rax),
                 ];
                 if let Some(idx) = jmp_idx {
-                    let address = internals.compute_address(idx);
+                    let address = internals.jump_address(idx);
                     output.push(
                         Inst::Inst1Op("jz", Op::Label(address))
                     );
                 } else {
                     err::Err::new(
-                        "Missing an `end` statement after the `if` or `do`. Should be in this format: `<condition> if <if_true> else <if_false> end`".to_string(),
+                        format!("Missing an `end` statement after the `{}`. Should be in this format: `<condition> if <if_true> else <if_false> end`", self),
                         internals.location.clone(),
-                        2,
+                        self.to_string().len(),
                     ).panic();
                 }
                 Ok(output)
             },
             Token::Else => {
                 if let Some(idx) = jmp_idx {
-                    let address = internals.compute_address(idx);
-                    let label = internals.compute_address(internals.idx);
-                    Ok(vec![
-                        Inst::Inst1Op("jmp", Op::Label(address)),
-                        Inst::Label(format!("{}:", label))
-                    ])
+                    let address = internals.jump_address(idx);
+                    let mut output = vec![Inst::Inst1Op("jmp", Op::Label(address))];
+                    output.append(&mut internals.labels());
+                    Ok(output)
                 } else {
                     err::Err::new(
                         "Missing an `end` statement after the `else`. Should be in this format: `<condition> if <if_true> else <if_false> end`".to_string(),
@@ -53,23 +97,89 @@ impl Token {
                     Ok(vec![])
                 }
             },
-            Token::While | Token::End => {
-                let address = internals.compute_address(internals.idx);
-                Ok(vec![Inst::Label(format!("{}:", address))])
-            },
+            Token::While | Token::End => Ok(internals.labels()),
             Token::EndWhile => {
                 let mut output = vec![];
                 if let Some(idx) = jmp_idx {
-                    let address = internals.compute_address(idx);
+                    let address = internals.jump_address(idx);
                     output.push(
                         Inst::Inst1Op("jmp", Op::Label(address))
                     );
                 }
-                let address = internals.compute_address(internals.idx);
-                output.push(Inst::Label(format!("{}:", address)));
+                output.append(&mut internals.labels());
+                Ok(output)
+            },
+            Token::Ret | Token::Break | Token::Continue => {
+                match jmp_idx {
+                    Some(idx) => Ok(vec![Inst::Inst1Op("jmp", Op::Label(internals.jump_address(idx)))]),
+                    None => {
+                        Err(err::Err::new(
+                            format!("`{}` is not inside a `fn` or a `while` loop.", self),
+                            internals.location.clone(),
+                            self.to_string().len(),
+                        ))
+                    }
+                }
+            },
+            Token::Case | Token::For => Ok(vec![]),
+            // Call the function pointer on the top of the stack.
+            Token::Call => Ok(vec![
+                Inst::Pop(Op::Rax),
+                Inst::Call(Op::Rax),
+            ]),
+            // Store the range of the `for` loop, then increment the index and
+            // push the condition of the loop like a `while` would. The index
+            // starts one before the first value, a `continue` jumps back here.
+            Token::As => {
+                let index = internals.loop_slot(internals.idx, false);
+                let end = internals.loop_slot(internals.idx, true);
+                let mut output = vec![
+                    Inst::Pop(Op::Rbx),
+                    Inst::Pop(Op::Rdx),
+                    Inst::Sub(Op::Rdx, Op::Immediate(1)),
+                ];
+                output.append(&mut internals.loop_frame());
+                output.append(&mut vec![
+                    Inst::Mov(end.clone(), Op::Rbx),
+                    Inst::Mov(index.clone(), Op::Rdx),
+                ]);
+                output.append(&mut internals.labels());
+                output.append(&mut internals.loop_frame());
+                output.append(&mut vec![
+                    Inst::Mov(Op::Rbx, index.clone()),
+                    Inst::Add(Op::Rbx, Op::Immediate(1)),
+                    Inst::Mov(index, Op::Rbx),
+                    Inst::Mov(Op::Rdx, end),
+                    Inst::Xor(Op::Rcx, Op::Rcx),
+                    Inst::Cmp(Op::Rbx, Op::Rdx),
+                    Inst::Mov(Op::Rdx, Op::Immediate(1)),
+                    Inst::Inst2Op("cmovl", Op::Rcx, Op::Rdx),
+                    Inst::Push(Op::Rcx),
+                ]);
+                Ok(output)
+            },
+            Token::Of(arms) => {
+                match jmp_idx {
+                    Some(default) => Ok(compile_case(arms, default, internals)),
+                    None => {
+                        Err(err::Err::new(
+                            "Missing an `end` statement after the `case`. Should be in this format: `case <value> of <value> => <body> ... else <body> end`".to_string(),
+                            internals.location.clone(),
+                            2,
+                        ))
+                    }
+                }
+            },
+            // The previous arm ends by jumping after the `case`.
+            Token::Arm => {
+                let mut output = vec![];
+                if let Some(idx) = jmp_idx {
+                    output.push(Inst::Inst1Op("jmp", Op::Label(internals.jump_address(idx))));
+                }
+                output.append(&mut internals.labels());
                 Ok(output)
             },
-            Token::Fn | Token::Const => {
+            Token::Fn | Token::Const | Token::Struct | Token::Memory | Token::Asm => {
                 if let Some(idx) = jmp_idx {
                     internals.idx = idx;
                 }
@@ -80,4 +190,44 @@ impl Token {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// Jump to the arm of a `case` matching the value on top of the stack, or to
+/// the `default` (the `else` or the `end`) if no arm matches. Dense values use
+/// a jump table, other values are compared one by one.
+fn compile_case(arms:&[(isize, usize)], default:usize, internals:&mut internals::Internals) -> Vec<Inst> {
+    let mut output = vec![Inst::Pop(Op::Rax)];
+    let default_address = internals.shared_address(default);
+    let min = arms.iter().map(|(value, _)| *value).min().unwrap_or(0);
+    let max = arms.iter().map(|(value, _)| *value).max().unwrap_or(0);
+    let range = (max as i128 - min as i128 + 1) as usize;
+    if arms.len() >= 4 && range <= arms.len() * 2 {
+        let table = format!("CASE_{}", internals.idx);
+        output.append(&mut vec![
+            Inst::Mov(Op::Rbx, Op::Immediate(min)),
+            Inst::Sub(Op::Rax, Op::Rbx),
+            Inst::Cmp(Op::Rax, Op::Immediate(range as isize - 1)),
+            Inst::Inst1Op("ja", Op::Label(default_address.clone())),
+            Inst::Lea(Op::Rbx, Op::Memory(format!("[{}]", table))),
+            Inst::Inst1Op("jmp", Op::Memory("[rbx + rax * 8]".to_string())),
+            Inst::Label(format!("{}:", table)),
+        ]);
+        for value in min..=max {
+            let target = match arms.iter().find(|(arm_value, _)| *arm_value == value) {
+                Some((_, arm)) => internals.shared_address(*arm),
+                None => default_address.clone(),
+            };
+            output.push(Inst::Inst1Op("dq", Op::Label(target)));
+        }
+    } else {
+        for (value, arm) in arms.iter() {
+            output.append(&mut vec![
+                Inst::Mov(Op::Rbx, Op::Immediate(*value)),
+                Inst::Cmp(Op::Rax, Op::Rbx),
+                Inst::Inst1Op("je", Op::Label(internals.shared_address(*arm))),
+            ]);
+        }
+        output.push(Inst::Inst1Op("jmp", Op::Label(default_address)));
+    }
+    output
+}
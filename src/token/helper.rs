@@ -15,7 +15,7 @@ impl Token {
     }
 
     pub fn new_type_annot(value:&str) -> Result<Token, String> {
-        let values = value.trim().split("->").collect::<Vec<&str>>();
+        let values = split_outside_brackets(value.trim(), "->");
         let mut args_type = values[0];
         let mut return_type = values[0].to_string();
         let mut ignore_return = false;
@@ -39,8 +39,8 @@ impl Token {
 
     fn parse_annot(value:&str) -> Result<Vec<Vec<type_checker::Typ>>, String> {
         let mut output = vec![];
-        for typ in value.split(",") {
-            if typ.trim().len() > 0 {
+        for typ in split_outside_brackets(value, ",") {
+            if !typ.trim().is_empty() {
                 match type_checker::Typ::try_from(typ.trim()) {
                     Ok(val) => output.push(val),
                     Err(err) => {
@@ -51,4 +51,25 @@ impl Token {
         }
         Ok(output)
     }
-}
\ No newline at end of file
+}
+
+/// Split `value` on `separator`, except inside brackets (the signature of a
+/// function type like `fn[int -> int]`).
+pub fn split_outside_brackets<'a>(value:&'a str, separator:&str) -> Vec<&'a str> {
+    let mut output = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (idx, c) in value.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            _ if depth == 0 && value[idx..].starts_with(separator) && idx >= start => {
+                output.push(&value[start..idx]);
+                start = idx + separator.len();
+            },
+            _ => {},
+        }
+    }
+    output.push(&value[start..]);
+    output
+}
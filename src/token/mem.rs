@@ -1,4 +1,6 @@
 use crate::compiler::{asm::*, internals, err};
+use crate::type_checker;
+use std::fmt;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Size {
@@ -8,13 +10,26 @@ pub enum Size {
     Byte
 }
 
+impl fmt::Display for Size {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        let size = match self {
+            Size::Qword => "qword",
+            Size::Dword => "dword",
+            Size::Word => "word",
+            Size::Byte => "byte",
+        };
+        write!(f, "{}", size)
+    }
+}
+
 impl Size {
-    pub fn to_string(&self) -> String {
+    /// The number of bytes loaded or stored with this size.
+    pub fn bytes(&self) -> usize {
         match self {
-            Size::Qword => "qword".to_string(),
-            Size::Dword => "dword".to_string(),
-            Size::Word => "word".to_string(),
-            Size::Byte => "byte".to_string(),
+            Size::Qword => 8,
+            Size::Dword => 4,
+            Size::Word => 2,
+            Size::Byte => 1,
         }
     }
 
@@ -28,6 +43,17 @@ impl Size {
     }
 }
 
+/// A field of a struct. `name` is the name used to access it (`Name.field`) and
+/// `offset` its position from the begining of the struct.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Field {
+    pub name:String,
+    pub structure:String,
+    pub offset:usize,
+    pub size:Size,
+    pub typ:type_checker::Typ,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
     InternalMem,
@@ -39,6 +65,18 @@ pub enum Token {
     Drop,
     Load(Size),
     Store(Size),
+    LoadField(Field),
+    StoreField(Field),
+    /// Reserve a named static buffer (`memory <identifier> <size> end`).
+    Reserve(String, usize),
+    /// Push a pointer to a static buffer reserved with `memory`.
+    Static(String),
+    /// Push the index (named by the identifier) of the `for` loop whose `as`
+    /// is at the given index.
+    LoopIndex(String, usize),
+    /// Push the address of a function (`&<identifier>`), it is called with
+    /// `call`.
+    FnAddress(String),
     Put,
     Fetch,
 }
@@ -52,6 +90,28 @@ impl Token {
                     Inst::Push(Op::Rax)
                 ])
             },
+            Token::Reserve(name, size) => {
+                internals.push_reservation(name.to_string(), *size);
+                Ok(vec![])
+            },
+            Token::Static(name) => {
+                Ok(vec![
+                    Inst::Lea(Op::Rax, Op::Memory(format!("[_static@{}]", name.replace("::", "_")))),
+                    Inst::Push(Op::Rax)
+                ])
+            },
+            Token::FnAddress(name) => {
+                Ok(vec![
+                    Inst::Lea(Op::Rax, Op::Memory(format!("[{}]", name.replace("::", "_")))),
+                    Inst::Push(Op::Rax)
+                ])
+            },
+            Token::LoopIndex(_, as_idx) => {
+                let index = internals.loop_slot(*as_idx, false);
+                let mut output = internals.loop_frame();
+                output.push(Inst::Push(index));
+                Ok(output)
+            },
             Token::Mem => {
                 Ok(vec![
                     Inst::Lea(Op::Rax, Op::Memory("[_mem@mem]".to_string())),
@@ -87,7 +147,7 @@ impl Token {
                 Ok(vec![
                     Inst::Xor(Op::Rbx, Op::Rbx),
                     Inst::Pop(Op::Rax),
-                    Inst::Mov(size.to_rbx(), Op::Memory(format!("{} [rax]", size.to_string()))),
+                    Inst::Mov(size.to_rbx(), Op::Memory(format!("{} [rax]", size))),
                     Inst::Push(Op::Rbx),
                 ])
             }
@@ -95,7 +155,22 @@ impl Token {
                 Ok(vec![
                     Inst::Pop(Op::Rax),
                     Inst::Pop(Op::Rbx),
-                    Inst::Mov(Op::Memory(format!("{} [rax]", size.to_string())), size.to_rbx()),
+                    Inst::Mov(Op::Memory(format!("{} [rax]", size)), size.to_rbx()),
+                ])
+            }
+            Token::LoadField(field) => {
+                Ok(vec![
+                    Inst::Xor(Op::Rbx, Op::Rbx),
+                    Inst::Pop(Op::Rax),
+                    Inst::Mov(field.size.to_rbx(), Op::Memory(format!("{} [rax + {}]", field.size, field.offset))),
+                    Inst::Push(Op::Rbx),
+                ])
+            }
+            Token::StoreField(field) => {
+                Ok(vec![
+                    Inst::Pop(Op::Rax),
+                    Inst::Pop(Op::Rbx),
+                    Inst::Mov(Op::Memory(format!("{} [rax + {}]", field.size, field.offset)), field.size.to_rbx()),
                 ])
             }
             Token::Drop => {
@@ -107,13 +182,7 @@ impl Token {
                 match &internals.current_variable {
                     Some(variable) => {
                         // If the variable does not exist, we create it.
-                        let idx = match internals.variables.iter().position(|r| r == variable) {
-                            Some(idx) => idx,
-                            _ => {
-                                internals.variables.push(variable.clone());
-                                internals.variables.len() - 1
-                            }
-                        };
+                        let idx = internals.variable_idx(variable.clone());
                         Ok(vec![
                             Inst::Push(Op::Immediate(idx as isize)),
                             Inst::Call(Op::Label("_std@put_variable".to_string())),
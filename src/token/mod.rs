@@ -3,4 +3,5 @@ pub mod mem;
 pub mod cmp;
 pub mod arith;
 pub mod sys;
-pub mod helper;
\ No newline at end of file
+pub mod helper;
+pub mod cast;
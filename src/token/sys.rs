@@ -1,41 +1,84 @@
-use std::collections::HashMap;
 use crate::compiler::{asm::*, internals, err};
+use crate::type_checker::Typ;
+
+/// A function of the OS or of a library, declared with
+/// `extern fn <identifier>[<type_annotation>]` and called with
+/// `sys::<identifier>`. Like for a `fn`, the first type of the annotation is the
+/// top of the stack, it is the first argument of the call.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Extern {
+    pub name:String,
+    pub args:Vec<Vec<Typ>>,
+    pub returns:Vec<Vec<Typ>>,
+}
+
+impl Extern {
+    /// The value returned by the function, `None` if it returns `void`.
+    pub fn returned(&self) -> Option<Typ> {
+        match self.returns.as_slice() {
+            [typ] if typ != &vec![Typ::Void] => Some(if typ.len() > 1 { Typ::Any } else { typ[0].clone() }),
+            _ => None,
+        }
+    }
+}
+
+/// The instructions of an `asm [<type_annotation>] "<instruction>" ... end`
+/// block. The instructions pop the arguments and push the returned values
+/// themselves, the type checker trusts the annotation (only the number of
+/// `push` and `pop` is checked against it).
+#[derive(Debug, PartialEq, Clone)]
+pub struct Asm {
+    pub args:Vec<Vec<Typ>>,
+    pub returns:Vec<Vec<Typ>>,
+    pub instructions:Vec<String>,
+}
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
+    /// A `sys::<identifier>` before it is matched with its declaration.
     Sys(String),
+    Call(Extern),
+    Extern,
+    /// An `export` before its function is parsed.
+    Export,
+    /// The native signature of the function declared just after, it can be
+    /// called by foreign code through its trampoline.
+    Exported(Extern),
+    /// The address of the trampoline of an exported function, `<name>.export`.
+    ExportAddress(String),
+    Asm(Asm),
     Include,
 }
 
 impl Token {
-    pub fn compile(&self, consts:&HashMap<String, isize>, internals:&mut internals::Internals) -> Result<Vec<Inst>, err::Err> {
+    pub fn compile(&self, internals:&mut internals::Internals) -> Result<Vec<Inst>, err::Err> {
         match self {
+            Token::Call(function) => {
+                internals.push_extern(function.name.clone());
+                let mut output = internals.convention.prologue(&function.args);
+                output.push(Inst::Call(Op::Label(function.name.clone())));
+                output.append(&mut internals.convention.epilogue(function.args.len(), function.returned()));
+                Ok(output)
+            },
             Token::Sys(sys) => {
-                match consts.get(sys) {
-                    Some(&args) => {
-                        let registers = [Op::Rcx, Op::Rdx, Op::R8, Op::R9];
-                        let args_count = if 4 < args as usize { 4 } else { args as usize };
-                        let mut output = (0..args_count).map(|idx| {
-                            Inst::Pop(registers[idx].clone())
-                        }).collect::<Vec<Inst>>();
-                        output.append(&mut vec![
-                            Inst::Call(Op::Label(sys.to_string())),
-                            Inst::Push(Op::Rax)
-                        ]);
-                        Ok(output)
-                    },
-                    _ => {
-                        Err(err::Err::new(
-                            format!("Before using a sys call, you must defined a const with it's number of arguments. The const must have the same name as the sys call does. `{}` has no const assossiated.", sys),
-                            internals.location.clone(),
-                            sys.len(),
-                        ))
-                    }
-                }
+                Err(err::Err::new(
+                    format!("`sys::{}` is not declared. Declare it with `extern fn {}[<type_annotation>]`.", sys, sys),
+                    internals.location.clone(),
+                    sys.len() + 5,
+                ))
             },
-            Token::Include => {
+            Token::ExportAddress(name) => {
+                Ok(vec![
+                    Inst::Lea(Op::Rax, Op::Memory(format!("[_export@{}]", name.replace("::", "_")))),
+                    Inst::Push(Op::Rax),
+                ])
+            },
+            Token::Asm(block) => {
+                Ok(block.instructions.iter().map(|inst| Inst::Raw(inst.clone())).collect())
+            },
+            Token::Extern | Token::Export | Token::Exported(_) | Token::Include => {
                 Ok(vec![])
             }
         }
     }
-}
\ No newline at end of file
+}
@@ -1,7 +1,9 @@
-use crate::parser::{token, typ};
+use crate::parser::{self, token, typ};
+use crate::parser::typ::{mem, helper};
 use crate::compiler::err;
 
 use std::collections::HashMap;
+use std::fmt;
 use colored::*;
 
 #[derive(Clone, PartialEq, Debug)]
@@ -9,49 +11,201 @@ pub enum Typ {
     Ptr,
     Int,
     Str,
+    /// A string with its length stored in the qword before its first char.
+    Slice,
     Void,
     Float,
     Any,
     Char,
+    Struct(String),
+    PtrTo(Box<Typ>),
+    Var(String),
+    /// A pointer to a function, with the types of its arguments and of its
+    /// returned values.
+    Fn(Vec<Vec<Typ>>, Vec<Vec<Typ>>),
 }
 
-impl Typ {
-    pub fn to_string(&self) -> String {
-        match self {
-            Typ::Ptr => "ptr".to_string(),
-            Typ::Str => "str".to_string(),
-            Typ::Int => "int".to_string(),
-            Typ::Void => "void".to_string(),
-            Typ::Float => "float".to_string(),
-            Typ::Any => "any".to_string(),
-            Typ::Char => "char".to_string(),
-        }
+impl fmt::Display for Typ {
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+        let typ = match self {
+            Typ::Ptr => "ptr",
+            Typ::Str => "str",
+            Typ::Slice => "slice",
+            Typ::Int => "int",
+            Typ::Void => "void",
+            Typ::Float => "float",
+            Typ::Any => "any",
+            Typ::Char => "char",
+            Typ::Struct(name) | Typ::Var(name) => name,
+            Typ::PtrTo(pointee) => {
+                return write!(f, "ptr<{}>", pointee);
+            },
+            Typ::Fn(args, returns) => {
+                let types = |types:&Vec<Vec<Typ>>| types.iter().map(|typ| {
+                    typ.iter().map(|t| t.to_string()).collect::<Vec<String>>().join("|")
+                }).collect::<Vec<String>>().join(", ");
+                return if args.is_empty() {
+                    write!(f, "fn[{}]", types(returns))
+                } else {
+                    write!(f, "fn[{} -> {}]", types(args), types(returns))
+                };
+            },
+        };
+        write!(f, "{}", typ)
     }
+}
 
+impl Typ {
+    /// Parse a type annotation, multiple types can be separated by `|`. A
+    /// single lowercase letter is a type variable (a generic type). Any other
+    /// identifier is a pointer to a struct, the parser checks later that the
+    /// struct is declared.
     pub fn try_from(value:&str) -> Result<Vec<Self>, String> {
         let mut output = vec![];
-        for v in value.split("|") {
+        for v in helper::split_outside_brackets(value, "|") {
             output.push(
                 match v {
+                    _ if v.len() == 1 && v.starts_with(|c:char| c.is_ascii_lowercase()) => {
+                        if value.contains('|') {
+                            return Err(format!("The type variable `{}` cannot be part of multiple types separated by `|`.", v));
+                        }
+                        Typ::Var(v.to_string())
+                    },
                     "ptr" => Typ::Ptr,
                     "int" => Typ::Int,
                     "str" => Typ::Str,
+                    "slice" => Typ::Slice,
                     "float" => Typ::Float,
                     "void" => Typ::Void,
                     "any" => Typ::Any,
                     "char" => Typ::Char,
+                    _ if v.starts_with("ptr<") && v.ends_with('>') => {
+                        match Typ::try_from(&v[4..v.len() - 1])?.as_slice() {
+                            [pointee] if pointee != &Typ::Void => Typ::PtrTo(Box::new(pointee.clone())),
+                            _ => {
+                                return Err(format!("A typed pointer must point to a single type (not `void`) like `ptr<int>` but {} was found.", v));
+                            }
+                        }
+                    },
+                    _ if v.starts_with("fn[") && v.ends_with(']') => {
+                        match helper::Token::new_type_annot(&v[3..v.len() - 1])? {
+                            helper::Token::TypeAnnot(args, returns, false) => Typ::Fn(args, returns),
+                            _ => {
+                                return Err(format!("A function type cannot ignore its return (`!`) but {} was found.", v));
+                            }
+                        }
+                    },
+                    _ if !v.is_empty() && v.chars().all(|c| c.is_alphanumeric() || c == '_' || c == ':') => {
+                        Typ::Struct(v.to_string())
+                    },
                     _ => {
-                        return Err(format!("Types can only be `ptr`, `int`, `str`, `float`, `void` or `any` but {} was found. You can also put multiple types separated by `|`.", v));
+                        return Err(format!("Types can only be `ptr`, `ptr<type>`, `fn[type -> type]`, `int`, `str`, `slice`, `float`, `void`, `any` or the name of a struct but {} was found. You can also put multiple types separated by `|`.", v));
                     }
                 }
             );
         }
         Ok(output)
     }
+
+    /// If the value is an address in memory.
+    pub fn is_pointer(&self) -> bool {
+        matches!(self, Typ::Ptr | Typ::Str | Typ::Slice | Typ::Struct(_) | Typ::PtrTo(_))
+    }
+
+    /// The size of the value when it is stored in memory. `any` and type
+    /// variables can be stored with any size.
+    pub fn size(&self) -> Option<mem::Size> {
+        match self {
+            Typ::Any | Typ::Var(_) => None,
+            Typ::Char => Some(mem::Size::Byte),
+            _ => Some(mem::Size::Qword),
+        }
+    }
+
+    /// The name of the struct used by this type, even behind typed pointers.
+    pub fn struct_name(&self) -> Option<&String> {
+        match self {
+            Typ::Struct(name) => Some(name),
+            Typ::PtrTo(pointee) => pointee.struct_name(),
+            Typ::Fn(args, returns) => args.iter().chain(returns.iter()).flatten().find_map(|typ| typ.struct_name()),
+            _ => None,
+        }
+    }
+
+    /// Bind the type variables of this (expected) type to the types found on
+    /// the stack. A type variable keeps the first type it is bound to, the
+    /// following uses are checked against it when the variables are replaced
+    /// (see `substitute`).
+    pub fn unify(&self, found:&Typ, bindings:&mut HashMap<String, Typ>) {
+        match (self, found) {
+            (Typ::Var(name), found) => {
+                bindings.entry(name.clone()).or_insert_with(|| found.clone());
+            },
+            (Typ::PtrTo(expected), Typ::PtrTo(found)) => expected.unify(found, bindings),
+            (Typ::Fn(expected_args, expected_returns), Typ::Fn(found_args, found_returns)) => {
+                let expected = expected_args.iter().chain(expected_returns.iter());
+                let found = found_args.iter().chain(found_returns.iter());
+                for (expected, found) in expected.zip(found) {
+                    if let ([expected], [found]) = (expected.as_slice(), found.as_slice()) {
+                        expected.unify(found, bindings);
+                    }
+                }
+            },
+            _ => {},
+        }
+    }
+
+    /// Replace the type variables by the types they are bound to. Unbound type
+    /// variables become `any`.
+    pub fn substitute(&self, bindings:&HashMap<String, Typ>) -> Typ {
+        match self {
+            Typ::Var(name) => bindings.get(name).cloned().unwrap_or(Typ::Any),
+            Typ::PtrTo(pointee) => Typ::PtrTo(Box::new(pointee.substitute(bindings))),
+            Typ::Fn(args, returns) => {
+                let substitute = |types:&Vec<Vec<Typ>>| types.iter().map(|typ| {
+                    typ.iter().map(|t| t.substitute(bindings)).collect()
+                }).collect();
+                Typ::Fn(substitute(args), substitute(returns))
+            },
+            typ => typ.clone(),
+        }
+    }
+
+    /// If a value of type `found` can be used where a value of this type is
+    /// expected. A typed pointer or a pointer to a struct can be used as a
+    /// `ptr` and a `ptr` can be used as any of them, but pointers to two
+    /// different types are not compatible.
+    pub fn is_compatible(&self, found:&Typ) -> bool {
+        match (self, found) {
+            (Typ::Any, _) | (_, Typ::Any) => true,
+            (Typ::Ptr, Typ::Struct(_)) | (Typ::Struct(_), Typ::Ptr) => true,
+            (Typ::Ptr, Typ::PtrTo(_)) | (Typ::PtrTo(_), Typ::Ptr) => true,
+            (Typ::PtrTo(expected), Typ::PtrTo(found)) => expected.is_compatible(found),
+            // Function pointers must have the same signature.
+            (Typ::Fn(expected_args, expected_returns), Typ::Fn(found_args, found_returns)) => {
+                let is_same = |expected:&Vec<Vec<Typ>>, found:&Vec<Vec<Typ>>| {
+                    expected.len() == found.len() && expected.iter().zip(found.iter()).all(|(expected, found)| {
+                        expected.len() == found.len() && expected.iter().zip(found.iter()).all(|(expected, found)| expected.is_compatible(found))
+                    })
+                };
+                is_same(expected_args, found_args) && is_same(expected_returns, found_returns)
+            },
+            (expected, found) => expected == found,
+        }
+    }
 }
 
 pub struct TypeChecker {
     pub stack:Vec<Typ>,
+    /// Set while checking a function that ignores its return (`!`). Those
+    /// functions can pop a variable number of values in a `while` loop.
+    pub ignore_return:bool,
+    /// The types returned by the function being checked, the stack is checked
+    /// against them at each `ret`.
+    pub returns:Vec<Vec<Typ>>,
+    /// Set when the current path left its block with a `ret`, a `break` or a
+    /// `continue`, the stack is not used until the end of the block.
+    diverged:bool,
     errors:Vec<err::Err>,
 }
 
@@ -59,6 +213,9 @@ impl TypeChecker {
     pub fn new() -> Self {
         Self {
             stack:vec![],
+            ignore_return:false,
+            returns:vec![],
+            diverged:false,
             errors:vec![],
         }
     }
@@ -75,7 +232,7 @@ impl TypeChecker {
             for typs in typs_allowed.iter() {
                 let typ = self.stack.pop().unwrap();
                 found_types.push(typ.clone());
-                if !typs.contains(&typ) && !typs.contains(&Typ::Any) && typ != Typ::Any {
+                if !typs.iter().any(|allowed| allowed.is_compatible(&typ)) {
                     error = true;
                 }
             }
@@ -110,7 +267,7 @@ impl TypeChecker {
         for typs in typs_allowed.iter() {
             let typ = self.stack.pop().unwrap();
             found_types.push(typ.clone());
-            if !typs.contains(&typ) && !typs.contains(&Typ::Any) && typ != Typ::Any {
+            if !typs.iter().any(|allowed| allowed.is_compatible(&typ)) {
                 err::Err::new(
                     format!(
                         "The function `{}` should return types of `{}` but returned types of `{}` on the stack.",
@@ -129,7 +286,7 @@ impl TypeChecker {
         let mut output = "".to_string();
         allowed.iter().for_each(|args| {
             args.iter().for_each(|arg| {
-                output.push_str(&format!("{}|", arg.to_string()));
+                output.push_str(&format!("{}|", arg));
             });
             output.pop();
             output.push_str(", ");
@@ -142,13 +299,219 @@ impl TypeChecker {
     pub fn found_types_to_string(&self, found:Vec<Typ>) -> String {
         let mut output = "".to_string();
         found.iter().for_each(|arg| {
-            output.push_str(&format!("{}, ", arg.to_string()));
+            output.push_str(&format!("{}, ", arg));
         });
         output.pop();
         output.pop();
         output
     }
 
+    /// Check if the two values on top of the stack are floats. Mixing a `float`
+    /// with an other type is an error, a conversion (`int>float` or
+    /// `float>int`) must be used first. When `true` is returned, both values
+    /// were popped off the stack.
+    pub fn is_float_operation(&mut self, keyword:&str, tok:&token::Token) -> bool {
+        let len = self.stack.len();
+        let operands = [&self.stack[len - 2], &self.stack[len - 1]];
+        if !operands.contains(&&Typ::Float) {
+            return false;
+        }
+        if operands.iter().any(|typ| *typ != &Typ::Float && *typ != &Typ::Any) {
+            self.errors.push(err::Err::new(
+                format!(
+                    "To use `{}` you need values of the same type on the stack but values of types `{}, {}` were found. Convert them first with `int>float` or `float>int`.",
+                    keyword, operands[1], operands[0],
+                ),
+                (tok.row, tok.col, tok.filename.clone()),
+                tok.len()
+            ));
+        }
+        self.stack.truncate(len - 2);
+        true
+    }
+
+    /// Check an arithmetic operation on chars. An `int` can be added to or
+    /// substracted from a `char` to get an other `char` and the difference of
+    /// two chars is an `int`. Any other operation on a `char` is an error, it
+    /// must be converted first with `char>int`. Return `None` if none of the
+    /// two values on top of the stack is a `char`, otherwise both values are
+    /// popped off the stack and the type of the result is returned.
+    pub fn char_operation(&mut self, arith:&typ::arith::Token, tok:&token::Token) -> Option<Typ> {
+        let len = self.stack.len();
+        let (lhs, rhs) = (self.stack[len - 2].clone(), self.stack[len - 1].clone());
+        if lhs != Typ::Char && rhs != Typ::Char {
+            return None;
+        }
+        self.stack.truncate(len - 2);
+        let is_int = |typ:&Typ| typ == &Typ::Int || typ == &Typ::Any;
+        match (&lhs, &rhs, arith) {
+            (Typ::Char, Typ::Char, typ::arith::Token::Minus) => Some(Typ::Int),
+            (Typ::Char, int, typ::arith::Token::Plus | typ::arith::Token::Minus) if is_int(int) => Some(Typ::Char),
+            (int, Typ::Char, typ::arith::Token::Plus) if is_int(int) => Some(Typ::Char),
+            _ => {
+                self.errors.push(err::Err::new(
+                    format!(
+                        "Only an `int` can be added to or substracted from a `char` (or a `char` from an other `char`) but values of types `{}, {}` were found. Convert the `char` first with `char>int`.",
+                        rhs, lhs,
+                    ),
+                    (tok.row, tok.col, tok.filename.clone()),
+                    tok.len()
+                ));
+                Some(Typ::Char)
+            }
+        }
+    }
+
+    /// Check that a value loaded or stored through a typed pointer has the same
+    /// size as the memory operation and that the stored value has the type the
+    /// pointer points to.
+    pub fn check_pointee(&mut self, size:&mem::Size, pointee:&Typ, value:Option<&Typ>, tok:&token::Token) {
+        if let Some(pointee_size) = pointee.size() {
+            if &pointee_size != size {
+                self.errors.push(err::Err::new(
+                    format!(
+                        "The pointer points to a value of type `{}` of {} bytes but {} bytes are accessed. Use the memory operation of the same size.",
+                        pointee, pointee_size.bytes(), size.bytes(),
+                    ),
+                    (tok.row, tok.col, tok.filename.clone()),
+                    tok.len()
+                ));
+            }
+        }
+        if let Some(value) = value {
+            // An `int` can be stored as a `char`, like a char literal.
+            let is_char = pointee == &Typ::Char && value == &Typ::Int;
+            if !pointee.is_compatible(value) && !is_char {
+                self.errors.push(err::Err::new(
+                    format!("The pointer points to a value of type `{}` but a value of type `{}` is stored.", pointee, value),
+                    (tok.row, tok.col, tok.filename.clone()),
+                    tok.len()
+                ));
+            }
+        }
+    }
+
+    /// Check the arguments of a call to a function and push its returned values.
+    /// The type variables are bound to the arguments found on the stack, then
+    /// the arguments and the returned values are checked and pushed with the
+    /// bound types.
+    pub fn check_call(&mut self, name:&str, args:&[Vec<Typ>], returns:&[Vec<Typ>], tok:&token::Token) {
+        let mut bindings = HashMap::new();
+        args.iter().zip(self.stack.iter().rev()).for_each(|(arg, found)| {
+            if arg.len() == 1 {
+                arg[0].unify(found, &mut bindings);
+            }
+        });
+        let args = args.iter().map(|arg| {
+            arg.iter().map(|t| t.substitute(&bindings)).collect()
+        }).collect();
+        self.check_stack(name, args, tok);
+        let ret_type = returns.iter().map(|t| {
+            if t.len() > 1 {
+                Typ::Any
+            } else {
+                t[0].substitute(&bindings)
+            }
+        }).collect::<Vec<Typ>>();
+        // The first returned type is the top of the stack.
+        if ret_type != vec![Typ::Void] {
+            self.stack.extend(ret_type.into_iter().rev());
+        }
+    }
+
+    /// Check that a branch (`if`, `else` or the body of a `while`) leaves the
+    /// stack as it is expected at the `end` keyword. The `if` branch without an
+    /// `else` and the `while` body must not change the stack and the `else`
+    /// branch must end the same way as the `if` branch.
+    pub fn check_branch(&mut self, opening:&typ::control_flow::Token, expected:Vec<Typ>, tok:&token::Token) {
+        let is_loop = matches!(opening, typ::control_flow::Token::While | typ::control_flow::Token::As);
+        if self.ignore_return && is_loop {
+            self.stack = expected;
+            return;
+        }
+        let is_same = expected.len() == self.stack.len()
+            && expected.iter().zip(self.stack.iter()).all(|(expected, found)| expected.is_compatible(found));
+        if !is_same {
+            let message = match opening {
+                typ::control_flow::Token::Else => "The `if` and `else` branches should leave the same values on the stack",
+                typ::control_flow::Token::Elif => "Every branch of an `elif` chain should leave the same values on the stack",
+                typ::control_flow::Token::Of(_) => "Every arm of a `case` should leave the same values on the stack",
+                typ::control_flow::Token::While => "The body of a `while` loop should not change the values on the stack",
+                typ::control_flow::Token::As => "The body of a `for` loop should not change the values on the stack",
+                _ => "An `if` without an `else` should not change the values on the stack",
+            };
+            self.errors.push(err::Err::new(
+                format!(
+                    "{}. Expected `{}` but `{}` was found.",
+                    message,
+                    self.found_types_to_string(expected.into_iter().rev().collect()),
+                    self.found_types_to_string(self.stack.iter().rev().cloned().collect()),
+                ),
+                (tok.row, tok.col, tok.filename.clone()),
+                tok.len()
+            ));
+        }
+    }
+
+    /// Check that the stack holds the values returned by the function at a
+    /// `ret`, like at the `end` of the function.
+    pub fn check_ret(&mut self, tok:&token::Token) {
+        if self.ignore_return {
+            return;
+        }
+        let returns = if self.returns == vec![vec![Typ::Void]] { vec![] } else { self.returns.clone() };
+        let is_same = returns.len() == self.stack.len()
+            && returns.iter().zip(self.stack.iter().rev()).all(|(allowed, found)| {
+                allowed.iter().any(|typ| typ.is_compatible(found))
+            });
+        if !is_same {
+            self.errors.push(err::Err::new(
+                format!(
+                    "`ret` should leave the values returned by the function on the stack. Expected `{}` but `{}` was found.",
+                    if returns.is_empty() { "void".to_string() } else { self.allowed_types_to_string(returns) },
+                    self.found_types_to_string(self.stack.iter().rev().cloned().collect()),
+                ),
+                (tok.row, tok.col, tok.filename.clone()),
+                tok.len()
+            ));
+        }
+    }
+
+    /// Check that a `break` or a `continue` leaves the stack as it was before
+    /// the loop, like the end of its body.
+    pub fn check_loop_exit(&mut self, keyword:&typ::control_flow::Token, expected:Vec<Typ>, tok:&token::Token) {
+        if self.ignore_return {
+            return;
+        }
+        let is_same = expected.len() == self.stack.len()
+            && expected.iter().zip(self.stack.iter()).all(|(expected, found)| expected.is_compatible(found));
+        if !is_same {
+            self.errors.push(err::Err::new(
+                format!(
+                    "`{}` should leave the values on the stack as they were before the loop. Expected `{}` but `{}` was found.",
+                    keyword,
+                    self.found_types_to_string(expected.into_iter().rev().collect()),
+                    self.found_types_to_string(self.stack.iter().rev().cloned().collect()),
+                ),
+                (tok.row, tok.col, tok.filename.clone()),
+                tok.len()
+            ));
+        }
+    }
+
+    /// Merge the stack left by an arm of a `case` with the stack left by the
+    /// previous arms, they must all be the same. An arm that left the block
+    /// (with a `ret`, a `break` or a `continue`) leaves no stack.
+    pub fn merge_arm(&mut self, result:&mut Option<Vec<Typ>>, tok:&token::Token) {
+        if self.diverged {
+            return;
+        }
+        match result {
+            Some(expected) => self.check_branch(&typ::control_flow::Token::Of(vec![]), expected.clone(), tok),
+            None => *result = Some(self.stack.clone()),
+        }
+    }
+
     pub fn check_stack_len(&self, keyword:&str, min_len:usize, tok:&token::Token) -> bool {
         if self.stack.len() < min_len {
             err::Err::new(
@@ -164,9 +527,9 @@ impl TypeChecker {
 
     pub fn checks(
         &mut self,
-        tokens:&Vec<token::Token>,
-        functions:&HashMap<String, (usize, usize, Vec<Vec<Typ>>, Vec<Vec<Typ>>, bool)>,
-        consts:&HashMap<String, isize>,
+        tokens:&mut [token::Token],
+        functions:&parser::Functions,
+        consts:&HashMap<String, parser::constant::Value>,
     ) {
         for (identifier, func) in functions.clone().into_iter() {
             let args: Vec<Typ> = func.2.iter().rev().map(|arg| {
@@ -177,12 +540,16 @@ impl TypeChecker {
                 }
             }).collect();
             self.stack = args;
-            self.check(&tokens, functions, consts, func.0, func.1, false);
-            if func.4 {
+            self.ignore_return = func.4;
+            self.returns = func.3.clone();
+            self.check(tokens, functions, consts, func.0, func.1, false);
+            self.ignore_return = false;
+            // Every path already returned with a `ret`, each one was checked.
+            if func.4 || self.diverged {
                 continue;
             }
             if func.3 == vec![vec![Typ::Void]] {
-                if self.stack.len() != 0 {
+                if !self.stack.is_empty() {
                     err::Err::new(
                         format!("`{}` should return an empty stack, but it returns {} values on the stack", identifier, self.stack.len()),
                         (tokens[func.0 - 2].row, tokens[func.0 - 2].col, tokens[func.0 - 2].filename.clone()), identifier.len()
@@ -198,21 +565,29 @@ impl TypeChecker {
             }
         }
         self.stack = vec![];
-        self.check(&tokens, functions, consts, 0, tokens.len(), false);
+        self.check(tokens, functions, consts, 0, tokens.len(), false);
     }
 
     pub fn check(
         &mut self,
-        tokens:&[token::Token],
-        functions:&HashMap<String, (usize, usize, Vec<Vec<Typ>>, Vec<Vec<Typ>>, bool)>,
-        consts:&HashMap<String, isize>,
+        tokens:&mut [token::Token],
+        functions:&parser::Functions,
+        consts:&HashMap<String, parser::constant::Value>,
         start:usize,
         end:usize,
         debug:bool,
     ) {
         let mut variables_types:HashMap<String, Typ> = HashMap::new();
         let mut current_variable:Option<String> = None;
+        // The stack expected at the `end` of each opened `if`, `else` or
+        // `while`. There is no expected stack for an `else` when the `if`
+        // branch left with a `ret`, a `break` or a `continue`.
+        let mut branches:Vec<(typ::control_flow::Token, Option<Vec<Typ>>)> = vec![];
+        // The stack left by the arms of each opened `case` and if it has an
+        // `else`.
+        let mut cases:Vec<(Option<Vec<Typ>>, bool)> = vec![];
         let mut idx = start;
+        self.diverged = false;
 
         if debug {
             println!(
@@ -225,21 +600,34 @@ impl TypeChecker {
         }
         while idx < end {
             let mut was_identifier = false;
+            let mut is_float = false;
             let token = &tokens[idx];
             match &tokens[idx].typ {
                 typ::Typ::Int(_) => self.stack.push(Typ::Int),
+                typ::Typ::Char(_) => self.stack.push(Typ::Char),
+                typ::Typ::Float(_) => self.stack.push(Typ::Float),
                 typ::Typ::Str(_) => self.stack.push(Typ::Str),
+                typ::Typ::Slice(_) => self.stack.push(Typ::Slice),
                 typ::Typ::Memory(tok) => {
                     match tok {
                         typ::mem::Token::Mem => self.stack.push(Typ::Ptr),
                         typ::mem::Token::InternalMem => self.stack.push(Typ::Ptr),
+                        typ::mem::Token::Static(_) => self.stack.push(Typ::Ptr),
+                        typ::mem::Token::LoopIndex(_, _) => self.stack.push(Typ::Int),
+                        typ::mem::Token::FnAddress(name) => {
+                            match functions.get(name) {
+                                Some(func) => self.stack.push(Typ::Fn(func.2.clone(), func.3.clone())),
+                                None => self.stack.push(Typ::Any),
+                            }
+                        },
+                        typ::mem::Token::Reserve(_, _) => {},
                         typ::mem::Token::Dup => {
-                            if self.check_stack_len("dup", 1, &token) {
+                            if self.check_stack_len("dup", 1, token) {
                                 self.stack.push(self.stack[self.stack.len() - 1].clone());
                             }
                         },
                         typ::mem::Token::Drop => {
-                            if self.check_stack("drop", vec![vec![Typ::Any]], &token) {
+                            if self.check_stack("drop", vec![vec![Typ::Any]], token) {
                             }
                         },
                         typ::mem::Token::Fetch => {
@@ -268,12 +656,21 @@ impl TypeChecker {
                             }
 
                         }
-                        typ::mem::Token::Load(_) => {
-                            self.check_stack("load", vec![vec![Typ::Ptr, Typ::Int]], &token);
-                            self.stack.push(Typ::Any);
+                        typ::mem::Token::Load(size) => {
+                            let ptr = self.stack.last().cloned().map(|ptr| {
+                                if ptr == Typ::Str || ptr == Typ::Slice { Typ::PtrTo(Box::new(Typ::Char)) } else { ptr }
+                            });
+                            self.check_stack("load", vec![vec![Typ::Ptr, Typ::Str, Typ::Slice, Typ::Int]], token);
+                            match ptr {
+                                Some(Typ::PtrTo(pointee)) => {
+                                    self.check_pointee(size, &pointee, None, token);
+                                    self.stack.push(*pointee);
+                                },
+                                _ => self.stack.push(Typ::Any),
+                            }
                         },
                         typ::mem::Token::Over => {
-                            if self.check_stack_len("over", 2, &token) {
+                            if self.check_stack_len("over", 2, token) {
                                 self.stack.push(self.stack[self.stack.len() - 2].clone());
                             }
                         },
@@ -301,19 +698,35 @@ impl TypeChecker {
                         }
                         typ::mem::Token::Rot => {
                             let len = self.stack.len();
-                            if self.check_stack_len("rot", 3, &token) {
+                            if self.check_stack_len("rot", 3, token) {
                                 let tmp = self.stack[self.stack.len() - 3].clone();
                                 self.stack[len - 3] = self.stack[self.stack.len() - 2].clone();
                                 self.stack[len - 2] = self.stack[self.stack.len() - 1].clone();
                                 self.stack[len - 1] = tmp;
                             }
                         }
-                        typ::mem::Token::Store(_) => {
-                            self.check_stack("store", vec![vec![Typ::Ptr, Typ::Int], vec![Typ::Any]], &token);
+                        typ::mem::Token::Store(size) => {
+                            let len = self.stack.len();
+                            let values = if len >= 2 { Some((self.stack[len - 1].clone(), self.stack[len - 2].clone())) } else { None };
+                            self.check_stack("store", vec![vec![Typ::Ptr, Typ::Int], vec![Typ::Any]], token);
+                            if let Some((Typ::PtrTo(pointee), value)) = values {
+                                self.check_pointee(size, &pointee, Some(&value), token);
+                            }
+                        }
+                        typ::mem::Token::LoadField(field) => {
+                            self.check_stack(&format!("{}@", field.name), vec![vec![Typ::Struct(field.structure.clone())]], token);
+                            self.stack.push(field.typ.clone());
+                        }
+                        typ::mem::Token::StoreField(field) => {
+                            let mut value = vec![field.typ.clone()];
+                            if field.typ == Typ::Char {
+                                value.push(Typ::Int);
+                            }
+                            self.check_stack(&format!("{}!", field.name), vec![vec![Typ::Struct(field.structure.clone())], value], token);
                         }
                         typ::mem::Token::Swap => {
                             let len = self.stack.len();
-                            if self.check_stack_len("swap", 2, &token) {
+                            if self.check_stack_len("swap", 2, token) {
                                 let tmp = self.stack[self.stack.len() - 2].clone();
                                 self.stack[len - 2] = self.stack[self.stack.len() - 1].clone();
                                 self.stack[len - 1] = tmp;
@@ -322,45 +735,189 @@ impl TypeChecker {
                     }
                 }
                 typ::Typ::Ignore => {},
-                typ::Typ::Arithmetic(_) => {
-                    let is_ptr = self.stack[self.stack.len() - 1] == Typ::Ptr || self.stack[self.stack.len() - 2] == Typ::Ptr;
-                    self.check_stack("arithmetic", vec![vec![Typ::Int, Typ::Ptr], vec![Typ::Int, Typ::Ptr]], &token);
-                    if is_ptr {
-                        self.stack.push(Typ::Ptr);
+                typ::Typ::Arithmetic(tok) => {
+                    self.check_stack_len("arithmetic", 2, token);
+                    if self.is_float_operation("arithmetic", token) {
+                        is_float = true;
+                        if !matches!(tok, typ::arith::Token::Plus | typ::arith::Token::Minus | typ::arith::Token::Mul | typ::arith::Token::Div) {
+                            self.errors.push(err::Err::new(
+                                "Only `+`, `-`, `*` and `/` can be used with values of type `float`.".to_string(),
+                                (token.row, token.col, token.filename.clone()),
+                                token.len()
+                            ));
+                        }
+                        self.stack.push(Typ::Float);
+                    } else if let Some(typ) = self.char_operation(tok, token) {
+                        self.stack.push(typ);
                     } else {
-                        self.stack.push(Typ::Int);
+                        // Offsetting a typed pointer keeps the type it points
+                        // to and offsetting a `str` gives a pointer to one of its
+                        // chars. Offsetting a struct or a `slice` gives a pointer
+                        // (the length of a slice is before its first char) and
+                        // the difference of two pointers is an `int`.
+                        let pointers = self.stack[self.stack.len() - 2..].iter().filter(|typ| typ.is_pointer()).cloned().collect::<Vec<Typ>>();
+                        self.check_stack("arithmetic", vec![vec![Typ::Int, Typ::Ptr, Typ::Str, Typ::Slice], vec![Typ::Int, Typ::Ptr, Typ::Str, Typ::Slice]], token);
+                        match (pointers.as_slice(), tok) {
+                            ([], _) | ([_, _], typ::arith::Token::Minus) => self.stack.push(Typ::Int),
+                            ([typ @ Typ::PtrTo(_)], _) => self.stack.push(typ.clone()),
+                            ([Typ::Str], _) => self.stack.push(Typ::PtrTo(Box::new(Typ::Char))),
+                            _ => self.stack.push(Typ::Ptr),
+                        }
                     }
                 }
                 typ::Typ::Comparison(_) => {
-                    self.check_stack("cmp", vec![vec![Typ::Int, Typ::Ptr], vec![Typ::Int, Typ::Ptr]], &token);
+                    self.check_stack_len("cmp", 2, token);
+                    if self.is_float_operation("cmp", token) {
+                        is_float = true;
+                    } else {
+                        self.check_stack("cmp", vec![vec![Typ::Int, Typ::Ptr, Typ::Char], vec![Typ::Int, Typ::Ptr, Typ::Char]], token);
+                    }
                     self.stack.push(Typ::Int);
                 }
+                typ::Typ::Cast(tok) => {
+                    let (from, to) = tok.types();
+                    self.check_stack(&tok.to_string(), vec![vec![from]], token);
+                    self.stack.push(to);
+                }
                 typ::Typ::ControlFlow(tok) => {
                     match tok {
-                        typ::control_flow::Token::If => {
-                            // TODO: Add internal check (both branch push the same thing)
-                            self.check_stack("if", vec![vec![Typ::Int]], &token);
+                        typ::control_flow::Token::If | typ::control_flow::Token::Elif => {
+                            self.check_stack(&tok.to_string(), vec![vec![Typ::Int]], token);
+                            branches.push((tok.clone(), Some(self.stack.clone())));
                         },
                         typ::control_flow::Token::Else => {
-                            if let Some(jmp_idx) = tokens[idx].jmp_idx {
-                                idx = jmp_idx;
+                            match branches.pop() {
+                                // The `else` of a `case` is its last arm.
+                                Some((opening @ typ::control_flow::Token::Of(_), Some(start))) => {
+                                    if let Some((result, has_else)) = cases.last_mut() {
+                                        self.merge_arm(result, token);
+                                        *has_else = true;
+                                    }
+                                    self.stack = start.clone();
+                                    self.diverged = false;
+                                    branches.push((opening, Some(start)));
+                                },
+                                // The `else` branch starts with the stack as it
+                                // was before the `if` and must end like the `if`
+                                // branch. The `else` of an `elif` is still part
+                                // of the chain.
+                                Some((opening, stack)) => {
+                                    let if_stack = if self.diverged { None } else { Some(self.stack.clone()) };
+                                    let opening = match opening {
+                                        typ::control_flow::Token::Elif => opening,
+                                        _ => tok.clone(),
+                                    };
+                                    branches.push((opening, if_stack));
+                                    self.stack = stack.unwrap_or_default();
+                                    self.diverged = false;
+                                },
+                                None => {},
                             }
                         }
-                        typ::control_flow::Token::Do => {
-                            self.check_stack("do", vec![vec![Typ::Int]], &token);
-                            if let Some(jmp_idx) = tokens[idx].jmp_idx {
-                                idx = jmp_idx;
+                        typ::control_flow::Token::Case => {},
+                        // The signature of the function pointer is checked like
+                        // a direct call.
+                        typ::control_flow::Token::Call => {
+                            self.check_stack_len("call", 1, token);
+                            match self.stack.pop() {
+                                Some(Typ::Fn(args, returns)) => self.check_call("call", &args, &returns, token),
+                                found => {
+                                    self.errors.push(err::Err::new(
+                                        format!(
+                                            "`call` needs a function pointer (like `fn[int -> int]`) on the top of the stack but `{}` was found.",
+                                            found.map(|typ| typ.to_string()).unwrap_or_default(),
+                                        ),
+                                        (token.row, token.col, token.filename.clone()),
+                                        token.len()
+                                    ));
+                                }
                             }
                         },
-                        typ::control_flow::Token::Fn | typ::control_flow::Token::Const => {
+                        typ::control_flow::Token::Of(_) => {
+                            self.check_stack("case", vec![vec![Typ::Int, Typ::Char]], token);
+                            branches.push((tok.clone(), Some(self.stack.clone())));
+                            cases.push((None, false));
+                        },
+                        // Every arm but the first one ends the previous arm.
+                        typ::control_flow::Token::Arm => {
+                            if let (Some(_), Some((_, Some(start)))) = (tokens[idx].jmp_idx, branches.last()) {
+                                let start = start.clone();
+                                if let Some((result, _)) = cases.last_mut() {
+                                    self.merge_arm(result, token);
+                                }
+                                self.stack = start;
+                                self.diverged = false;
+                            }
+                        },
+                        typ::control_flow::Token::While => {
+                            branches.push((tok.clone(), Some(self.stack.clone())));
+                        }
+                        typ::control_flow::Token::For => {},
+                        // The range of the loop is popped and the condition
+                        // is pushed for the `do`.
+                        typ::control_flow::Token::As => {
+                            self.check_stack("for", vec![vec![Typ::Int], vec![Typ::Int]], token);
+                            branches.push((tok.clone(), Some(self.stack.clone())));
+                            self.stack.push(Typ::Int);
+                        }
+                        typ::control_flow::Token::Do => {
+                            self.check_stack("do", vec![vec![Typ::Int]], token);
+                        },
+                        typ::control_flow::Token::End | typ::control_flow::Token::EndWhile => {
+                            // The `end` of an `elif` chain closes every branch
+                            // of the chain.
+                            let mut is_chained = true;
+                            while is_chained {
+                                let branch = branches.pop();
+                                is_chained = matches!(branch, Some((typ::control_flow::Token::Elif, _)));
+                                match branch {
+                                    Some((typ::control_flow::Token::Of(_), Some(start))) => {
+                                        let (mut result, has_else) = cases.pop().unwrap_or_default();
+                                        self.merge_arm(&mut result, token);
+                                        // Without an `else`, no arm is run when
+                                        // no value matches.
+                                        if !has_else {
+                                            self.stack = start.clone();
+                                            self.diverged = false;
+                                            self.merge_arm(&mut result, token);
+                                        }
+                                        self.diverged = result.is_none();
+                                        self.stack = result.unwrap_or(start);
+                                    },
+                                    // The `if` branch left the block, the stack is
+                                    // the one of the `else` branch.
+                                    Some((_, None)) | None => {},
+                                    Some((_, Some(stack))) if self.diverged => {
+                                        self.stack = stack;
+                                        self.diverged = false;
+                                    },
+                                    Some((opening, Some(stack))) => self.check_branch(&opening, stack, token),
+                                }
+                            }
+                        }
+                        typ::control_flow::Token::Ret => {
+                            self.check_ret(token);
+                            self.diverged = true;
+                        }
+                        typ::control_flow::Token::Break | typ::control_flow::Token::Continue => {
+                            let expected = branches.iter().rev().find_map(|(opening, stack)| {
+                                match opening {
+                                    typ::control_flow::Token::While | typ::control_flow::Token::As => stack.clone(),
+                                    _ => None,
+                                }
+                            });
+                            if let Some(expected) = expected {
+                                self.check_loop_exit(tok, expected, token);
+                            }
+                            self.diverged = true;
+                        }
+                        typ::control_flow::Token::Fn | typ::control_flow::Token::Const
+                        | typ::control_flow::Token::Struct => {
                             // TODO: Add internal check (check with what goes in and what goes out)
                             if let Some(jmp_idx) = tokens[idx].jmp_idx {
                                 idx = jmp_idx;
                             }
                         },
-                        typ::control_flow::Token::While => {
-                            // TODO: Add internal check (no more value after and before), internal stack ?
-                        }
                         _ => {}
                     }
                 }
@@ -369,51 +926,41 @@ impl TypeChecker {
                     was_identifier = true;
                     let id = identifier.to_string();
                     if let Some(func) = functions.get(&id) {
-                        self.check_stack(identifier, func.2.clone(), &token);
-                        let mut ret_type = func.3.clone().iter().map(|t| {
-                            if t.len() > 1 {
-                                Typ::Any
-                            } else {
-                                t[0].clone()
-                            }
-                        }).collect::<Vec<Typ>>();
-                        if ret_type != vec![Typ::Void] {
-                            self.stack.append(&mut ret_type);
-                        }
-                    } else if let Some(_) = consts.get(&id) {
-                        self.stack.push(Typ::Int);
+                        self.check_call(identifier, &func.2, &func.3, token);
+                    } else if let Some(value) = consts.get(&id) {
+                        self.stack.push(value.typ());
                     } else {
                         current_variable = Some(identifier.to_string());
                     }
                 }
-                typ::Typ::Sys(typ::sys::Token::Sys(sys)) => {
-                    if let Some(value) = consts.get(sys) {
-                        if value > &0 {
-                            let values_to_pop = (0..*value as usize).map(|_| vec![Typ::Any]).collect();
-                            self.check_stack(sys, values_to_pop, &token);
-                        }
-                        self.stack.push(Typ::Any);
-                    }
+                typ::Typ::Sys(typ::sys::Token::ExportAddress(_)) => self.stack.push(Typ::Ptr),
+                typ::Typ::Sys(typ::sys::Token::Call(function)) => {
+                    self.check_call(&format!("sys::{}", function.name), &function.args, &function.returns, token);
+                }
+                // The instructions are not checked, only the annotation.
+                typ::Typ::Sys(typ::sys::Token::Asm(block)) => {
+                    self.check_call("asm", &block.args, &block.returns, token);
                 }
                 _ => {}
             }
             if !was_identifier {
                 current_variable = None;
             }
+            tokens[idx].is_float = is_float;
             // TODO: Create a debug tool to show the stack like this, very
             // useful to debug some functions.
             if debug {
                 println!(
-                    "{: <24} {} {}",
+                    "{: <24} {} {:?}",
                     format!("{:?}", tokens[idx].typ),
                     "|".cyan(),
-                    format!("{:?}", self.stack),
+                    self.stack,
                 );
             }
             idx += 1;
         }
 
-        if self.errors.len() > 0 {
+        if !self.errors.is_empty() {
             self.errors.iter().for_each(|err| {
                 err.print();
             });
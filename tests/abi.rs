@@ -0,0 +1,242 @@
+mod common;
+
+use std::fs;
+use std::process::Command;
+
+/// Compile `source` with the System V calling convention and return the
+/// generated assembly.
+fn build_sysv(name:&str, source:&str) -> String {
+    let dir = common::build_dir(name);
+    fs::write(dir.join("main.rk"), source).unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_rack"))
+        .args(["main.rk", "--abi", "sysv"])
+        .current_dir(&dir)
+        .status()
+        .unwrap();
+    assert!(status.success(), "`{}` did not compile", name);
+    let output = fs::read_to_string(dir.join("output.asm")).unwrap();
+    fs::remove_dir_all(dir).unwrap();
+    output
+}
+
+/// The instructions generated for the call to the extern function `name`, from
+/// the location comment of the call to the next token.
+fn call_instructions(output:&str, name:&str) -> Vec<String> {
+    output.lines()
+        .skip_while(|line| !(line.starts_with("; ") && line.ends_with(&format!(" sys::{}", name))))
+        .skip(1)
+        .filter(|line| !line.starts_with("%line"))
+        .take_while(|line| line.starts_with('\t'))
+        .map(|line| line.trim().to_string())
+        .collect()
+}
+
+#[test]
+fn register_arguments_have_shadow_space() {
+    let output = common::build_source("abi_registers", "
+extern fn Three[int, ptr, int -> int]
+3 _mem 1 sys::Three drop
+");
+    assert_eq!(call_instructions(&output, "Three"), vec![
+        "mov rbx, rsp",
+        "and rsp, -16",
+        "sub rsp, 32",
+        "mov rcx, qword [rbx + 0]",
+        "mov rdx, qword [rbx + 8]",
+        "mov r8, qword [rbx + 16]",
+        "call Three",
+        "lea rsp, [rbx + 24]",
+        "push rax",
+    ]);
+}
+
+#[test]
+fn stack_arguments_are_after_the_shadow_space() {
+    let output = common::build_source("abi_stack", "
+extern fn Six[int, int, int, int, int, int -> int]
+6 5 4 3 2 1 sys::Six drop
+");
+    assert_eq!(call_instructions(&output, "Six"), vec![
+        "mov rbx, rsp",
+        "and rsp, -16",
+        "sub rsp, 48",
+        "mov rax, qword [rbx + 32]",
+        "mov qword [rsp + 32], rax",
+        "mov rax, qword [rbx + 40]",
+        "mov qword [rsp + 40], rax",
+        "mov rcx, qword [rbx + 0]",
+        "mov rdx, qword [rbx + 8]",
+        "mov r8, qword [rbx + 16]",
+        "mov r9, qword [rbx + 24]",
+        "call Six",
+        "lea rsp, [rbx + 48]",
+        "push rax",
+    ]);
+}
+
+#[test]
+fn stack_stays_aligned_with_an_odd_number_of_stack_arguments() {
+    let output = common::build_source("abi_odd", "
+extern fn Five[int, int, int, int, int -> int]
+5 4 3 2 1 sys::Five drop
+");
+    let instructions = call_instructions(&output, "Five");
+    assert!(instructions.contains(&"sub rsp, 48".to_string()), "{:?}", instructions);
+    assert!(instructions.contains(&"mov qword [rsp + 32], rax".to_string()), "{:?}", instructions);
+}
+
+#[test]
+fn floats_are_passed_and_returned_in_xmm_registers() {
+    let output = common::build_source("abi_float", "
+extern fn Scale[int, float -> float]
+2.0 3 sys::Scale drop
+");
+    assert_eq!(call_instructions(&output, "Scale"), vec![
+        "mov rbx, rsp",
+        "and rsp, -16",
+        "sub rsp, 32",
+        "mov rcx, qword [rbx + 0]",
+        "mov rdx, qword [rbx + 8]",
+        "movq xmm1, rdx",
+        "call Scale",
+        "lea rsp, [rbx + 16]",
+        "movq rax, xmm0",
+        "push rax",
+    ]);
+}
+
+#[test]
+fn void_functions_push_nothing() {
+    let output = common::build_source("abi_void", "
+extern fn Nothing[int -> void]
+0 sys::Nothing
+");
+    let instructions = call_instructions(&output, "Nothing");
+    assert_eq!(instructions.last().map(|line| line.as_str()), Some("lea rsp, [rbx + 8]"));
+    assert!(output.contains("\textern Nothing\n"));
+}
+
+#[test]
+fn sysv_arguments_are_in_their_own_registers() {
+    let output = build_sysv("abi_sysv_registers", "
+extern fn Mix[int, float, ptr, float -> float]
+2.0 _mem 1.0 3 sys::Mix drop
+");
+    assert_eq!(call_instructions(&output, "Mix"), vec![
+        "mov rbx, rsp",
+        "and rsp, -16",
+        "mov rdi, qword [rbx + 0]",
+        "movq xmm0, qword [rbx + 8]",
+        "mov rsi, qword [rbx + 16]",
+        "movq xmm1, qword [rbx + 24]",
+        "mov rax, 2",
+        "call Mix",
+        "lea rsp, [rbx + 32]",
+        "movq rax, xmm0",
+        "push rax",
+    ]);
+}
+
+#[test]
+fn sysv_stack_arguments_have_no_shadow_space() {
+    let output = build_sysv("abi_sysv_stack", "
+extern fn Seven[int, int, int, int, int, int, int -> int]
+7 6 5 4 3 2 1 sys::Seven drop
+");
+    assert_eq!(call_instructions(&output, "Seven"), vec![
+        "mov rbx, rsp",
+        "and rsp, -16",
+        "sub rsp, 16",
+        "mov rax, qword [rbx + 48]",
+        "mov qword [rsp + 0], rax",
+        "mov rdi, qword [rbx + 0]",
+        "mov rsi, qword [rbx + 8]",
+        "mov rdx, qword [rbx + 16]",
+        "mov rcx, qword [rbx + 24]",
+        "mov r8, qword [rbx + 32]",
+        "mov r9, qword [rbx + 40]",
+        "mov rax, 0",
+        "call Seven",
+        "lea rsp, [rbx + 56]",
+        "push rax",
+    ]);
+}
+
+#[test]
+fn unknown_conventions_are_rejected() {
+    let dir = common::build_dir("abi_unknown");
+    fs::write(dir.join("main.rk"), "1 drop\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_rack"))
+        .args(["main.rk", "--abi", "cdecl"])
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    fs::remove_dir_all(dir).unwrap();
+    let output = String::from_utf8_lossy(&output.stdout);
+    assert!(output.contains("The `--abi` flag expects `win64` or `sysv`."), "{}", output);
+}
+
+#[test]
+fn exported_functions_have_a_native_trampoline() {
+    let output = common::build_source("abi_export", "
+export fn pick[int, float, int, int, int -> float]
+    drop swap drop swap drop swap drop
+end
+pick.export drop
+");
+    let trampoline:Vec<&str> = output.lines()
+        .skip_while(|line| *line != "_export@pick:")
+        .skip(1)
+        .map(|line| line.trim())
+        .take_while(|line| *line != "ret")
+        .collect();
+    assert_eq!(trampoline, vec![
+        "push rbx", "push rbp", "push rsi", "push rdi", "push r12", "push r13", "push r14", "push r15",
+        "mov rbp, rsp",
+        "push qword [rbp + 104]",
+        "push r9",
+        "push r8",
+        "movq rax, xmm1",
+        "push rax",
+        "push rcx",
+        "call pick",
+        "pop rax",
+        "movq xmm0, rax",
+        "mov rsp, rbp",
+        "pop r15", "pop r14", "pop r13", "pop r12", "pop rdi", "pop rsi", "pop rbp", "pop rbx",
+    ]);
+    assert!(output.contains("\tglobal _export@pick\n"));
+    assert!(output.contains("\tlea rax, [_export@pick]\n"));
+}
+
+#[test]
+fn sysv_trampolines_read_their_own_registers() {
+    let output = build_sysv("abi_sysv_export", "
+export fn pick[int, float, int, int, int, int, int, int -> void]
+    drop drop drop drop drop drop drop drop
+end
+pick.export drop
+");
+    let trampoline:Vec<&str> = output.lines()
+        .skip_while(|line| *line != "_export@pick:")
+        .skip(1)
+        .map(|line| line.trim())
+        .take_while(|line| *line != "ret")
+        .collect();
+    assert_eq!(trampoline, vec![
+        "push rbx", "push rbp", "push rsi", "push rdi", "push r12", "push r13", "push r14", "push r15",
+        "mov rbp, rsp",
+        "push qword [rbp + 72]",
+        "push r9",
+        "push r8",
+        "push rcx",
+        "push rdx",
+        "push rsi",
+        "movq rax, xmm0",
+        "push rax",
+        "push rdi",
+        "call pick",
+        "mov rsp, rbp",
+        "pop r15", "pop r14", "pop r13", "pop r12", "pop rdi", "pop rsi", "pop rbp", "pop rbx",
+    ]);
+}
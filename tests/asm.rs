@@ -0,0 +1,61 @@
+mod common;
+
+#[test]
+fn instructions_are_spliced() {
+    let output = common::build_source("asm_splice", "
+1 asm [int -> int] \"pop rax\" \"rdtsc\" \"push rax\" end drop
+");
+    assert!(output.contains("\tpop rax\n\trdtsc\n\tpush rax\n"), "{}", output);
+}
+
+#[test]
+fn declared_effect_is_checked() {
+    let messages = common::build_errors("asm_effect", "
+\"a\" asm [int -> int] \"pop rax\" \"push rax\" end drop
+");
+    assert!(messages.contains("`asm`"), "{}", messages);
+}
+
+#[test]
+fn rsp_can_only_be_pushed_and_popped() {
+    let messages = common::build_errors("asm_rsp", "asm [void] \"sub rsp, 8\" end\n");
+    assert!(messages.contains("changes `rsp`"), "{}", messages);
+    let messages = common::build_errors("asm_pop_rsp", "asm [void] \"pop rsp\" end\n");
+    assert!(messages.contains("changes `rsp`"), "{}", messages);
+    common::build_source("asm_push_rsp", "asm [int] \"push rsp\" end drop\n");
+}
+
+#[test]
+fn rbp_is_rejected() {
+    let messages = common::build_errors("asm_rbp", "asm [void] \"mov rax, rbp\" end\n");
+    assert!(messages.contains("uses `rbp`"), "{}", messages);
+}
+
+#[test]
+fn pushes_and_pops_match_the_declared_effect() {
+    let messages = common::build_errors("asm_count", "1 asm [int -> int] \"pop rax\" end drop\n");
+    assert!(messages.contains("change the number of values on the stack by -1 but its type annotation declares 0"), "{}", messages);
+    let messages = common::build_errors("asm_count_void", "asm [void] \"push rax\" end\n");
+    assert!(messages.contains("by 1 but its type annotation declares 0"), "{}", messages);
+    common::build_source("asm_count_flags", "asm [int] \"pushfq\" end drop\n");
+}
+
+#[test]
+fn calls_and_frames_are_rejected() {
+    for (name, instruction) in [("asm_call", "call _std@exit"), ("asm_ret", "ret"), ("asm_leave", "leave"), ("asm_enter", "enter 16, 0")] {
+        let messages = common::build_errors(name, &format!("asm [void] \"{}\" end\n", instruction));
+        assert!(messages.contains("without being a `push` or a `pop`"), "{}", messages);
+    }
+}
+
+#[test]
+fn annotation_types_must_be_declared() {
+    let messages = common::build_errors("asm_unknown_type", "asm [Nope -> int] \"pop rax\" \"push rax\" end drop\n");
+    assert!(messages.contains("The type `Nope` used in the type annotation of an `asm` block is not a type or a declared struct."), "{}", messages);
+    assert!(messages.contains("| asm [Nope -> int]"), "{}", messages);
+    assert!(messages.contains("|     ^^^^^^^^^^^^^\n"), "{}", messages);
+    common::build_source("asm_struct_type", "
+_mem asm [Point -> Point] \"pop rax\" \"push rax\" end drop
+struct Point x:int y:int end
+");
+}
@@ -0,0 +1,34 @@
+mod common;
+
+#[test]
+fn if_and_else_leave_the_same_values() {
+    let output = common::build_errors(
+        "if_else_shapes",
+        "fn main[void] 1 if 1 else 1 2 end drop end\n",
+    );
+    assert!(output.contains("The `if` and `else` branches should leave the same values on the stack"), "{}", output);
+    common::build_source("if_else_same_shapes", "fn main[void] 1 if 1 else 2 end drop end\n");
+}
+
+#[test]
+fn if_without_else_keeps_the_stack() {
+    let output = common::build_errors("if_shape", "fn main[void] 1 if 2 end drop end\n");
+    assert!(output.contains("An `if` without an `else` should not change the values on the stack"), "{}", output);
+}
+
+#[test]
+fn while_body_keeps_the_stack() {
+    let output = common::build_errors(
+        "while_shape",
+        "fn main[void] 1 while dup 0 > do 1 end drop end\n",
+    );
+    assert!(output.contains("The body of a `while` loop should not change the values on the stack"), "{}", output);
+}
+
+#[test]
+fn functions_ignoring_their_return_can_pop_in_a_loop() {
+    common::build_source(
+        "ignore_return_shape",
+        "fn f[int -> !void] while dup 0 > do drop end end\nfn main[void] 0 1 f drop end\n",
+    );
+}
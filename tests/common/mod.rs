@@ -0,0 +1,49 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A new directory for a build, the compiler writes `output.asm` in the
+/// current directory.
+pub fn build_dir(name:&str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rack_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Compile `filename` in `dir` and return the generated assembly.
+pub fn build(dir:&Path, filename:&str) -> String {
+    let status = Command::new(env!("CARGO_BIN_EXE_rack"))
+        .arg(filename)
+        .current_dir(dir)
+        .status()
+        .unwrap();
+    assert!(status.success(), "`{}` did not compile", filename);
+    fs::read_to_string(dir.join("output.asm")).unwrap()
+}
+
+/// Compile a program written in `source` and return the generated assembly.
+#[allow(dead_code)]
+pub fn build_source(name:&str, source:&str) -> String {
+    let dir = build_dir(name);
+    fs::write(dir.join("main.rk"), source).unwrap();
+    let output = build(&dir, "main.rk");
+    fs::remove_dir_all(dir).unwrap();
+    output
+}
+
+/// Compile a program written in `source` that does not compile and return the
+/// messages of the compiler.
+#[allow(dead_code)]
+pub fn build_errors(name:&str, source:&str) -> String {
+    let dir = build_dir(name);
+    fs::write(dir.join("main.rk"), source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_rack"))
+        .arg("main.rk")
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    assert!(!dir.join("output.asm").exists(), "`{}` should not compile", name);
+    fs::remove_dir_all(dir).unwrap();
+    String::from_utf8(output.stdout).unwrap()
+}
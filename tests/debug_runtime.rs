@@ -0,0 +1,53 @@
+mod common;
+
+use std::fs;
+use std::process::Command;
+
+/// Compile a program written in `source` with `--debug-runtime` and return the
+/// generated assembly.
+fn build_debug(name:&str, source:&str) -> String {
+    let dir = common::build_dir(name);
+    fs::write(dir.join("main.rk"), source).unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_rack"))
+        .args(["main.rk", "--debug-runtime"])
+        .current_dir(&dir)
+        .status()
+        .unwrap();
+    assert!(status.success(), "`{}` did not compile", name);
+    let output = fs::read_to_string(dir.join("output.asm")).unwrap();
+    fs::remove_dir_all(dir).unwrap();
+    output
+}
+
+#[test]
+fn pops_are_guarded() {
+    let output = build_debug("debug_pop", "1 drop\n");
+    assert!(output.contains("\tcmp rsp, qword [_mem@stack_base]\n\tjb GUARD_"), "{}", output);
+}
+
+#[test]
+fn stored_variables_are_guarded() {
+    // The value stored by `put` is popped by `_std@put_variable`, below the
+    // index of the variable.
+    let output = build_debug("debug_put", "1 x put\n");
+    assert!(
+        output.contains("\tlea rax, [rsp + 8]\n\tcmp rax, qword [_mem@stack_base]\n\tjb GUARD_")
+            && output.contains("\tcall _std@put_variable\n"),
+        "{}", output
+    );
+}
+
+#[test]
+fn extern_calls_are_guarded() {
+    // `drain` ignores its return and leaves nothing, so `sys::One` takes its
+    // argument below the data stack.
+    let output = build_debug("debug_extern", "
+extern fn One[int -> int]
+fn drain[int, int -> !int] drop drop end
+1 2 drain sys::One drop
+");
+    assert!(
+        output.contains("\tlea rsp, [rbx + 8]\n\tcmp rsp, qword [_mem@stack_base]\n\tjbe GUARD_"),
+        "{}", output
+    );
+}
@@ -0,0 +1,46 @@
+use std::fs;
+use std::process::Command;
+
+mod common;
+
+#[test]
+fn functions_and_consts_are_documented() {
+    let dir = common::build_dir("doc");
+    fs::write(dir.join("lib.rk"), "\
+# === === ===
+# Numbers
+# === === ===
+# The answer, used by `lib::answer`.
+const ANSWER 42 end
+
+# Push the answer. Same as lib::twice but only once.
+# USAGE:
+#   lib::answer() -> int
+fn lib::answer[int]
+    ANSWER
+end
+
+fn lib::twice[int -> int]
+    2 *
+end
+
+fn lib::_internal[void] end
+").unwrap();
+    fs::write(dir.join("main.rk"), "include \"lib.rk\"\n\n# The entry point.\nfn main[void]\n    lib::answer drop\nend\n").unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_rack"))
+        .args(["doc", "main.rk"])
+        .current_dir(&dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let doc = fs::read_to_string(dir.join("doc.md")).unwrap();
+    assert!(doc.contains("## `lib.rk`"), "{}", doc);
+    assert!(doc.contains("<a id=\"lib-answer\"></a>\n\n### `lib::answer`\n\n```\nfn lib::answer[int]\n```\n"), "{}", doc);
+    assert!(doc.contains("\nPush the answer. Same as [`lib::twice`](#lib-twice) but only once.\n"), "{}", doc);
+    assert!(doc.contains("Usage:\n\n```\nlib::answer() -> int\n```\n"), "{}", doc);
+    assert!(doc.contains("```\nconst ANSWER 42 end\n```\n\nThe answer, used by [`lib::answer`](#lib-answer).\n"), "{}", doc);
+    assert!(!doc.contains("Numbers"), "the section title is not documentation");
+    assert!(!doc.contains("_internal"), "internal functions are not documented");
+    assert!(doc.contains("### `main`"), "{}", doc);
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,22 @@
+mod common;
+
+#[test]
+fn signature_types_must_be_declared() {
+    let output = common::build_errors("extern_unknown_type", "extern fn Foo[Nope -> int]\n");
+    assert!(output.contains("The type `Nope` used in the type annotation of `Foo` is not a type or a declared struct."), "{}", output);
+    common::build_source("extern_struct_type", "
+struct Point x:int y:int end
+extern fn Move[Point, int -> Point]
+");
+}
+
+#[test]
+fn export_signature_types_must_be_declared() {
+    let output = common::build_errors("export_unknown_type", "
+export fn pick[Nope -> int]
+    drop 1
+end
+pick.export drop
+");
+    assert!(output.contains("The type `Nope` used in the type annotation of `pick` is not a type or a declared struct."), "{}", output);
+}
@@ -0,0 +1,112 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+mod common;
+
+/// Run `rack fmt` with the given arguments and return whether it succeeded.
+fn fmt(args:&[&str]) -> bool {
+    Command::new(env!("CARGO_BIN_EXE_rack"))
+        .arg("fmt")
+        .args(args)
+        .output()
+        .unwrap()
+        .status
+        .success()
+}
+
+#[test]
+fn blocks_are_indented() {
+    let dir = common::build_dir("fmt_blocks");
+    let file = dir.join("main.rk");
+    fs::write(&file, "\
+# The absolute value
+fn abs[int -> int]
+  dup 0 < if
+\t0 swap -   # negate
+  end
+end
+case 2 of
+1 => \"one  1\" std::println_str
+else
+\"other\"   std::println_str
+end
+").unwrap();
+    let file = file.to_str().unwrap();
+    assert!(!fmt(&["--check", file]), "`--check` should fail on an unformatted file");
+    assert!(fmt(&[file]));
+    assert_eq!(fs::read_to_string(file).unwrap(), "\
+# The absolute value
+fn abs[int -> int]
+    dup 0 < if
+        0 swap -   # negate
+    end
+end
+case 2 of
+    1 => \"one  1\" std::println_str
+else
+    \"other\" std::println_str
+end
+");
+    assert!(fmt(&["--check", file]), "a formatted file should pass `--check`");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn sources_are_formatted() {
+    let sources = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let mut args = vec![];
+    for entry in fs::read_dir(sources).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|extension| extension == "rk") {
+            args.push(path.to_str().unwrap().to_string());
+        }
+    }
+    args.sort();
+    let mut check = vec!["--check"];
+    check.extend(args.iter().map(String::as_str));
+    assert!(fmt(&check), "run `rack fmt tests/*.rk`");
+}
+
+#[test]
+fn words_are_split_like_the_compiler_does() {
+    let dir = common::build_dir("fmt_tokens");
+    let file = dir.join("main.rk");
+    // `<if` is the comparison and the `if`, it opens a block.
+    fs::write(&file, "\
+fn f[int -> int]
+dup 0 <if
+  drop   s\"a  b\"   &g call
+end
+end
+").unwrap();
+    assert!(fmt(&[file.to_str().unwrap()]));
+    assert_eq!(fs::read_to_string(&file).unwrap(), "\
+fn f[int -> int]
+    dup 0 <if
+        drop s\"a  b\" &g call
+    end
+end
+");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn line_endings_are_kept() {
+    let dir = common::build_dir("fmt_crlf");
+    let file = dir.join("main.rk");
+    fs::write(&file, "fn f[void]\r\n1 drop\r\nend\r\n\r\n\r\n").unwrap();
+    assert!(fmt(&[file.to_str().unwrap()]));
+    assert_eq!(fs::read_to_string(&file).unwrap(), "fn f[void]\r\n    1 drop\r\nend\r\n");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn files_that_do_not_tokenize_are_not_changed() {
+    let dir = common::build_dir("fmt_error");
+    let file = dir.join("main.rk");
+    fs::write(&file, "fn f[void]\n\"abc\nend\n").unwrap();
+    assert!(!fmt(&[file.to_str().unwrap()]));
+    assert_eq!(fs::read_to_string(&file).unwrap(), "fn f[void]\n\"abc\nend\n");
+    fs::remove_dir_all(dir).unwrap();
+}
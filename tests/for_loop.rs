@@ -0,0 +1,28 @@
+mod common;
+
+#[test]
+fn index_is_read_only() {
+    let messages = common::build_errors("for_put", "for 0 3 as i do 5 i put end\n");
+    assert!(messages.contains("`i` is the index of a `for` loop, it is read-only."), "{}", messages);
+    let messages = common::build_errors("for_put_args", "for 0 3 as i do put(i) end\n");
+    assert!(messages.contains("`i` is the index of a `for` loop, it is read-only."), "{}", messages);
+    common::build_source("for_fetch", "for 0 3 as i do i! drop end\n");
+}
+
+#[test]
+fn index_is_kept_for_each_call() {
+    let output = common::build_source("for_depth", "
+fn nested[int -> void]
+    for 0 3 as i do
+        dup 0 > if dup 1 - nested end
+    end
+    drop
+end
+nested(2)
+");
+    // The index is read from the slots of the current depth of calls, not
+    // from a variable shared by every call.
+    assert!(output.contains("\tmov rax, qword [_mem@ret_ptr_idx]\n\tlea rcx, [_mem@loops]\n"), "{}", output);
+    assert!(output.contains("\tmov qword [rcx + rax * 8 + 0 * _mem@loop_frame], rbx\n"), "{}", output);
+    assert!(!output.contains("_for@"), "{}", output);
+}
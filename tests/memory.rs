@@ -0,0 +1,17 @@
+mod common;
+
+#[test]
+fn memory_names_are_unique() {
+    let messages = common::build_errors("memory_fn", "memory buffer 8 end\nfn buffer[void] end\n");
+    assert!(messages.contains("The memory `buffer` has the same name as a function"), "{}", messages);
+    let messages = common::build_errors("memory_const", "memory buffer 8 end\nconst buffer 8 end\n");
+    assert!(messages.contains("The memory `buffer` has the same name as a const"), "{}", messages);
+    let messages = common::build_errors("memory_variable", "memory buffer 8 end\n1 buffer put\n");
+    assert!(messages.contains("The memory `buffer` has the same name as a variable"), "{}", messages);
+}
+
+#[test]
+fn memories_are_resolved_by_name() {
+    let output = common::build_source("memory_static", "memory buffer 8 end\n1 buffer store\n");
+    assert!(output.contains("lea rax, [_static@buffer]"), "{}", output);
+}
@@ -0,0 +1,42 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Copy the rack sources of `tests/` in a new directory, the compiler writes
+/// `output.asm` in the current directory.
+fn build_dir(name:&str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rack_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let sources = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    for entry in fs::read_dir(sources).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|extension| extension == "rk") {
+            fs::copy(&path, dir.join(path.file_name().unwrap())).unwrap();
+        }
+    }
+    dir
+}
+
+fn build(dir:&Path, filename:&str) -> Vec<u8> {
+    let status = Command::new(env!("CARGO_BIN_EXE_rack"))
+        .arg(filename)
+        .current_dir(dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    fs::read(dir.join("output.asm")).unwrap()
+}
+
+#[test]
+fn builds_are_reproducible() {
+    let first_dir = build_dir("reproducible_first");
+    let second_dir = build_dir("reproducible_second");
+    let first = build(&first_dir, "tests.rk");
+    let second = build(&second_dir, "tests.rk");
+    let again = build(&first_dir, "tests.rk");
+    assert!(first == second, "two builds of `tests.rk` produced different assembly");
+    assert!(first == again, "rebuilding `tests.rk` produced different assembly");
+    fs::remove_dir_all(first_dir).unwrap();
+    fs::remove_dir_all(second_dir).unwrap();
+}
@@ -0,0 +1,52 @@
+mod common;
+
+#[test]
+fn identical_literals_share_a_label() {
+    let output = common::build_source("strings_interned", "
+\"hello\" drop
+\"world\" drop
+\"hello\" drop
+");
+    assert_eq!(output.matches("db \"hello\",0").count(), 1);
+    assert_eq!(output.matches("lea rax, [str_0]").count(), 2);
+}
+
+#[test]
+fn literals_are_read_only() {
+    let output = common::build_source("strings_rdata", "\"hello\" drop\n");
+    let rdata = output.find("segment .rdata").expect("no `.rdata` segment") + "segment .rdata".len();
+    let string = output.find("db \"hello\",0").unwrap();
+    assert!(rdata < string);
+    assert!(!output[rdata..string].contains("segment"), "the string is not in `.rdata`");
+}
+
+#[test]
+fn control_characters_are_rejected() {
+    let messages = common::build_errors("strings_control", "\"a\u{1}b\" drop\n");
+    assert!(messages.contains("cannot contain the control character"), "{}", messages);
+}
+
+#[test]
+fn slice_literals_share_the_label_of_strings() {
+    let output = common::build_source("strings_slice", "
+\"hello\" drop
+s\"hello\" drop
+");
+    assert_eq!(output.matches("lea rax, [str_0]").count(), 2);
+    assert!(output.contains("\tdq 5\n\tstr_0 db \"hello\",0\n"), "the length is not before the string");
+}
+
+#[test]
+fn foreign_code_cannot_give_a_str() {
+    let messages = common::build_errors("strings_extern_str", "extern fn GetCommandLineA[str]\n");
+    assert!(messages.contains("The extern function `GetCommandLineA` cannot return a `str`"), "{}", messages);
+    let messages = common::build_errors("strings_export_str", "export fn greet[str -> void] drop end\n");
+    assert!(messages.contains("The export function `greet` cannot take a `str` argument"), "{}", messages);
+}
+
+#[test]
+fn unchecked_pointer_conversions_are_named() {
+    let messages = common::build_errors("strings_ptr_slice", "_mem 8 + ptr>slice drop\n");
+    assert!(messages.contains("`ptr>slice` is free standing"), "{}", messages);
+    common::build_source("strings_ptr_slice_unchecked", "_mem 8 + ptr>slice_unchecked drop\n");
+}
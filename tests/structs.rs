@@ -0,0 +1,20 @@
+mod common;
+
+#[test]
+fn field_types_must_be_declared() {
+    let output = common::build_errors("struct_unknown_field", "struct S x:Nope end\n");
+    assert!(output.contains("The type `Nope` used by the field `S.x` is not a type or a declared struct."), "{}", output);
+    assert!(output.contains("| struct S x:Nope end\n"), "{}", output);
+}
+
+#[test]
+fn fields_can_use_any_declared_struct() {
+    common::build_source("struct_known_field", "
+struct Node
+    value:int
+    next:Node
+    pair:Pair
+end
+struct Pair a:int b:int end
+");
+}